@@ -0,0 +1,282 @@
+use neural_net::config::Config;
+use neural_net::data::loader::{load_mnist_from_dir, MNIST_DIR};
+use neural_net::data::split::split_validation;
+use neural_net::error::Result;
+use neural_net::metrics::accuracy::evaluate_with_loss;
+use neural_net::metrics::calibration::{fit_temperature, CalibrationReport};
+use neural_net::metrics::predictions::Predictions;
+use neural_net::metrics::history::EpochMetrics;
+use neural_net::training::checkpoint::{load_checkpoint, save_checkpoint, save_model, scheduled_checkpoint, Checkpoint};
+use neural_net::training::early_stopping::EarlyStopping;
+use neural_net::training::regularization::Regularizer;
+use neural_net::training::scheduler::LrScheduler;
+use neural_net::training::trainer::train_epoch;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: nn-cli --config <file.toml|file.json> [options]
+       nn-cli --resume <checkpoint.json> [options]
+
+Options:
+  --config <path>        Training configuration (TOML or JSON)
+  --resume <path>        Continue the run saved in a checkpoint, with its configuration
+  --model-out <path>     Where to save the trained model [default: trained_model.json]
+  --metrics-out <path>   Where to save per-epoch metrics as JSON [default: metrics.json]
+  --data-dir <path>      Directory holding the MNIST IDX files [default: data]
+  --evaluate-test        Evaluate the final model on the test set
+  -h, --help             Print this message";
+
+/// Exit code for a missing or invalid configuration.
+const EXIT_INVALID_CONFIG: u8 = 2;
+
+struct Args {
+    config: Option<PathBuf>,
+    resume: Option<PathBuf>,
+    model_out: PathBuf,
+    metrics_out: PathBuf,
+    data_dir: String,
+    evaluate_test: bool,
+}
+
+fn parse_args() -> std::result::Result<Args, String> {
+    let mut config = None;
+    let mut resume = None;
+    let mut model_out = PathBuf::from("trained_model.json");
+    let mut metrics_out = PathBuf::from("metrics.json");
+    let mut data_dir = MNIST_DIR.to_string();
+    let mut evaluate_test = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--config" => config = Some(PathBuf::from(value()?)),
+            "--resume" => resume = Some(PathBuf::from(value()?)),
+            "--model-out" => model_out = PathBuf::from(value()?),
+            "--metrics-out" => metrics_out = PathBuf::from(value()?),
+            "--data-dir" => data_dir = value()?,
+            "--evaluate-test" => evaluate_test = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    match (&config, &resume) {
+        (None, None) => return Err("--config or --resume is required".to_string()),
+        (Some(_), Some(_)) => return Err("--config and --resume cannot be combined".to_string()),
+        _ => {}
+    }
+
+    Ok(Args {
+        config,
+        resume,
+        model_out,
+        metrics_out,
+        data_dir,
+        evaluate_test,
+    })
+}
+
+fn read_config(path: &Path) -> std::result::Result<Config, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let config: Config = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?,
+        Some("json") => serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?,
+        _ => return Err(format!("{}: expected a .toml or .json file", path.display())),
+    };
+    config.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(config)
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_INVALID_CONFIG);
+        }
+    };
+
+    let checkpoint = match (&args.config, &args.resume) {
+        (Some(path), _) => read_config(path).map(|config| (config, None)).map_err(|e| format!("invalid config: {}", e)),
+        (None, Some(path)) => load_checkpoint(path)
+            .map(|checkpoint| (checkpoint.config.clone(), Some(checkpoint)))
+            .map_err(|e| format!("invalid checkpoint: {}: {}", path.display(), e)),
+        (None, None) => unreachable!("parse_args requires --config or --resume"),
+    };
+    let (config, checkpoint) = match checkpoint {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_INVALID_CONFIG);
+        }
+    };
+
+    match run(&config, checkpoint, &args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Trains from `checkpoint`, or from a new network when there is none.
+fn run(config: &Config, checkpoint: Option<Checkpoint>, args: &Args) -> Result<()> {
+    let (samples, test_set) = load_mnist_from_dir(&args.data_dir)?;
+    let (train_set, validation_set) = split_validation(samples, config.validation_split, config.stratify_validation)?;
+    println!(
+        "Loaded {} training, {} validation and {} test samples",
+        train_set.len(),
+        validation_set.len(),
+        test_set.len()
+    );
+
+    let mut run = match checkpoint {
+        Some(checkpoint) => {
+            println!("Resuming from epoch {} of {}", checkpoint.epoch + 1, config.epochs);
+            checkpoint
+        }
+        None => Checkpoint::start(config)?,
+    };
+    println!("{}", run.network.summary());
+    let loss = config.loss.build();
+    let regularizer = Regularizer::new(config, &run.network)?;
+
+    for epoch in run.epoch..config.epochs {
+        let skipped = run.guard.skipped_batches;
+        let learning_rate = train_epoch(
+            &mut run.network,
+            &train_set,
+            epoch,
+            config,
+            &mut run.optimizer,
+            &run.scheduler,
+            &mut run.guard,
+            &mut run.rng,
+        )?;
+        if run.guard.skipped_batches > skipped {
+            if let Some(first) = &run.guard.first {
+                eprintln!(
+                    "warning: skipped {} non-finite batches this epoch (first of the run: {})",
+                    run.guard.skipped_batches - skipped,
+                    first
+                );
+            }
+        }
+        let penalty = regularizer.penalty(&run.network);
+        let (train_accuracy, train_loss) = evaluate_with_loss(&run.network, &train_set, loss.as_ref())?;
+        let train_loss = train_loss + penalty;
+        let validation = if validation_set.is_empty() {
+            None
+        } else {
+            let (accuracy, validation_loss) = evaluate_with_loss(&run.network, &validation_set, loss.as_ref())?;
+            Some((accuracy, validation_loss + penalty))
+        };
+        // without a validation set, the training metrics drive the scheduler and early stopping
+        let (monitored_accuracy, monitored_loss) = validation.unwrap_or((train_accuracy, train_loss));
+        run.scheduler.end_epoch(monitored_accuracy);
+
+        let validation_summary = match validation {
+            Some((accuracy, validation_loss)) => format!(" | val acc {:.2}% loss {:.4}", accuracy, validation_loss),
+            None => String::new(),
+        };
+        println!(
+            "Epoch {}/{}: train acc {:.2}% loss {:.4}{} | penalty {:.4} | lr {:.6}",
+            epoch + 1,
+            config.epochs,
+            train_accuracy,
+            train_loss,
+            validation_summary,
+            penalty,
+            learning_rate
+        );
+        run.history.push(EpochMetrics {
+            epoch: epoch + 1,
+            train_accuracy,
+            validation_accuracy: validation.map(|(accuracy, _)| accuracy),
+            train_loss,
+            validation_loss: validation.map(|(_, validation_loss)| validation_loss),
+            penalty,
+            learning_rate,
+        });
+
+        let stopped = match run.early_stopping.as_mut() {
+            Some(early_stopping) => {
+                early_stopping.end_epoch(epoch, monitored_loss, monitored_accuracy, &run.network, &run.optimizer)
+            }
+            None => None,
+        };
+        run.epoch = epoch + 1;
+        if let Some(path) = scheduled_checkpoint(config, run.epoch) {
+            save_checkpoint(&path, &run)?;
+            println!("Saved checkpoint to {}", path.display());
+        }
+        if let Some(stop) = stopped {
+            println!("Stopped early at epoch {}: {}", stop.epoch + 1, stop);
+            break;
+        }
+    }
+
+    let mut network = run.network;
+    let mut optimizer = run.optimizer;
+    if let Some((best_epoch, best, best_optimizer)) = run.early_stopping.as_mut().and_then(EarlyStopping::take_best) {
+        network = best;
+        optimizer = best_optimizer;
+        println!("Restored the weights from epoch {}", best_epoch + 1);
+    }
+
+    let temperature = if config.temperature_scaling {
+        let predictions = Predictions::from_network(&network, &validation_set)?;
+        let temperature = fit_temperature(&predictions);
+        let before = CalibrationReport::new(&predictions);
+        let after = CalibrationReport::new(&predictions.with_temperature(temperature));
+        println!(
+            "Temperature scaling: T = {:.4}, val NLL {:.4} -> {:.4}, ECE {:.4} -> {:.4}",
+            temperature,
+            before.negative_log_likelihood,
+            after.negative_log_likelihood,
+            before.expected_calibration_error,
+            after.expected_calibration_error
+        );
+        Some(temperature)
+    } else {
+        None
+    };
+
+    if args.evaluate_test {
+        let (test_accuracy, test_loss) = evaluate_with_loss(&network, &test_set, loss.as_ref())?;
+        println!(
+            "Test: acc {:.2}% loss {:.4}",
+            test_accuracy,
+            test_loss + regularizer.penalty(&network)
+        );
+        let mut predictions = Predictions::from_network(&network, &test_set)?;
+        if let Some(temperature) = temperature {
+            predictions = predictions.with_temperature(temperature);
+        }
+        let report = CalibrationReport::new(&predictions);
+        let top_k: Vec<String> =
+            report.top_k_accuracy.iter().map(|(k, accuracy)| format!("top-{} {:.2}%", k, accuracy)).collect();
+        println!(
+            "Test calibration: {} | NLL {:.4} | Brier {:.4} | ECE {:.4} | MCE {:.4}",
+            top_k.join(", "),
+            report.negative_log_likelihood,
+            report.brier_score,
+            report.expected_calibration_error,
+            report.maximum_calibration_error
+        );
+    }
+
+    save_model(&args.model_out, &network, Some(&optimizer), temperature)?;
+    std::fs::write(&args.metrics_out, serde_json::to_string_pretty(&run.history)?)?;
+    println!("Saved model to {} and metrics to {}", args.model_out.display(), args.metrics_out.display());
+
+    Ok(())
+}
@@ -1,11 +1,91 @@
+use crate::data::split::ValidationSplit;
+use crate::error::{NeuralNetError, Result};
+use crate::network::activation::Activation;
+use crate::network::conv::{shape_len, ImageShape, Window};
+use crate::network::initializer::Initializer;
+use crate::network::normalization::Normalization;
+use crate::network::{Network, NetworkBuilder};
+use crate::utils::math::{seeded_rng, NetRng};
+use crate::training::early_stopping::EarlyStoppingConfig;
+use crate::training::loss::LossConfig;
+use crate::training::optimizer::OptimizerConfig;
+use crate::training::parallel::ParallelMode;
+use crate::training::regularization::expand_coefficients;
+use crate::training::scheduler::SchedulerConfig;
+use crate::training::stability::DivergencePolicy;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Config {
     pub epochs: usize,
     pub learning_rate: f32,
+    pub batch_size: usize,
+    pub optimizer: OptimizerConfig,
+    pub scheduler: SchedulerConfig,
+    pub loss: LossConfig,
+    pub label_smoothing: f32,
+    /// Dropout rate applied after every hidden layer; `0` disables dropout.
+    pub dropout: f32,
+    /// Normalization applied after every hidden layer, before its dropout.
+    pub normalization: Normalization,
+    /// L1 penalty coefficient per weighted layer (convolutions, then dense
+    /// layers), or a single value for all of them. Empty disables it.
+    pub l1: Vec<f32>,
+    /// L2 penalty coefficient, given like `l1`.
+    pub l2: Vec<f32>,
+    /// Decoupled weight decay: after every step the regularized parameters
+    /// shrink by `learning_rate * weight_decay` of their value.
+    pub weight_decay: f32,
+    /// Also regularize biases, PReLU slopes and normalization parameters
+    /// instead of only weights.
+    pub regularize_all_parameters: bool,
+    /// Every gradient is clamped to `[-clip_value, clip_value]`; `0` disables it.
+    pub clip_value: f32,
+    /// Gradients are scaled down together when their global L2 norm exceeds
+    /// `clip_norm`; `0` disables it.
+    pub clip_norm: f32,
+    /// What to do with a batch whose loss, activations or gradients are not finite.
+    pub on_divergence: DivergencePolicy,
+    /// Worker threads training together; `1` trains on the calling thread.
+    pub threads: usize,
+    /// How the workers share training when `threads` is above one.
+    pub parallel_mode: ParallelMode,
+    /// Training samples held out for validation. Validation metrics drive
+    /// early stopping and the scheduler, or training metrics when there is
+    /// no validation set; the test set is only evaluated on demand.
+    pub validation_split: ValidationSplit,
+    /// Hold out the same share of every class.
+    pub stratify_validation: bool,
+    /// Stops training when the validation metrics stop improving; `None` runs
+    /// every epoch.
+    pub early_stopping: Option<EarlyStoppingConfig>,
+    /// After training, fit a softmax temperature on the validation set that
+    /// calibrates the output probabilities. Needs a validation split and a
+    /// softmax output layer.
+    pub temperature_scaling: bool,
+    /// Saves a checkpoint to `checkpoint_dir` every this many epochs; `0`
+    /// disables it.
+    pub checkpoint_every: usize,
+    pub checkpoint_dir: String,
+    /// Shape of every sample as `(channels, height, width)`; sizes `conv_layers`.
+    pub input_shape: ImageShape,
+    /// Convolution and pooling layers applied to the inputs before the dense
+    /// layers. When not empty, their output is flattened and `layers[0]` must
+    /// be its size.
+    pub conv_layers: Vec<ConvLayerConfig>,
     pub layers: Vec<usize>,
     pub activations: Vec<String>,
+    /// Weight initializer per dense layer, e.g. `he_normal`. Empty uses
+    /// `Initializer::Auto` for every layer.
+    pub weight_init: Vec<String>,
+    /// Bias initializer per dense layer, e.g. `zeros`. Empty uses
+    /// `Initializer::Auto` for every layer.
+    pub bias_init: Vec<String>,
+    /// Seeds weight initialization and shuffling. Runs with the same seed are
+    /// reproducible; `None` draws a fresh seed every run.
+    pub seed: Option<u64>,
 }
 
 impl Default for Config {
@@ -13,8 +93,351 @@ impl Default for Config {
         Config {
             epochs: 20,
             learning_rate: 0.1,
+            batch_size: 1,
+            optimizer: OptimizerConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            loss: LossConfig::default(),
+            label_smoothing: 0.0,
+            dropout: 0.0,
+            normalization: Normalization::None,
+            l1: Vec::new(),
+            l2: Vec::new(),
+            weight_decay: 0.0,
+            regularize_all_parameters: false,
+            clip_value: 0.0,
+            clip_norm: 0.0,
+            on_divergence: DivergencePolicy::Halt,
+            threads: 1,
+            parallel_mode: ParallelMode::Synchronous,
+            validation_split: ValidationSplit::default(),
+            stratify_validation: false,
+            early_stopping: None,
+            temperature_scaling: false,
+            checkpoint_every: 0,
+            checkpoint_dir: "checkpoints".to_string(),
+            input_shape: (1, 28, 28),
+            conv_layers: Vec::new(),
             layers: vec![784, 128, 64, 10],
-            activations: vec!["sigmoid".to_string(), "sigmoid".to_string(), "sigmoid".to_string()],
+            activations: vec!["sigmoid".to_string(), "sigmoid".to_string(), "softmax".to_string()],
+            weight_init: Vec::new(),
+            bias_init: Vec::new(),
+            seed: None,
         }
     }
 }
+
+impl Config {
+    /// LeNet-5 for 28x28 MNIST digits: two tanh convolutions with average
+    /// pooling, followed by dense layers of 120 and 84 neurons.
+    pub fn lenet5() -> Self {
+        let conv = |channels, padding| ConvLayerConfig::Conv2d {
+            channels,
+            kernel_size: 5,
+            stride: 1,
+            padding,
+            dilation: 1,
+            activation: "tanh".to_string(),
+        };
+        let pool = ConvLayerConfig::AvgPool2d { kernel_size: 2, stride: 2 };
+        Config {
+            input_shape: (1, 28, 28),
+            conv_layers: vec![conv(6, 2), pool.clone(), conv(16, 0), pool],
+            layers: vec![400, 120, 84, 10],
+            activations: vec!["tanh".to_string(), "tanh".to_string(), "softmax".to_string()],
+            ..Config::default()
+        }
+    }
+
+    /// The generator for a run with this configuration, seeded from `seed`.
+    pub fn rng(&self) -> NetRng {
+        seeded_rng(self.seed)
+    }
+
+    /// Parses `activations`, rejecting unknown names.
+    pub fn parse_activations(&self) -> Result<Vec<Activation>> {
+        self.activations
+            .iter()
+            .map(|s| s.parse().map_err(NeuralNetError::from))
+            .collect()
+    }
+
+    /// Parses `weight_init` into one initializer per dense layer.
+    pub fn parse_weight_init(&self) -> Result<Vec<Initializer>> {
+        self.parse_initializers(&self.weight_init)
+    }
+
+    /// Parses `bias_init` into one initializer per dense layer.
+    pub fn parse_bias_init(&self) -> Result<Vec<Initializer>> {
+        self.parse_initializers(&self.bias_init)
+    }
+
+    fn parse_initializers(&self, names: &[String]) -> Result<Vec<Initializer>> {
+        if names.is_empty() {
+            return Ok(vec![Initializer::Auto; self.layers.len().saturating_sub(1)]);
+        }
+        names.iter().map(|s| s.parse().map_err(NeuralNetError::from)).collect()
+    }
+
+    /// Builds the network described by `conv_layers`, `layers`, `activations`,
+    /// the initializers, `normalization` and `dropout`, drawing its weights
+    /// from `rng`. Normalization and dropout only follow hidden dense layers.
+    pub fn build_network<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Network> {
+        self.validate()?;
+        let layers = self.parse_activations()?
+            .into_iter()
+            .zip(self.parse_weight_init()?)
+            .zip(self.parse_bias_init()?)
+            .zip(&self.layers[1..])
+            .enumerate();
+        let output_index = self.layers.len() - 2;
+
+        let mut builder = if self.conv_layers.is_empty() {
+            Network::builder().input(self.layers[0])
+        } else {
+            let (channels, height, width) = self.input_shape;
+            let builder = Network::builder().input_shape(channels, height, width);
+            let builder = self.conv_layers.iter().try_fold(builder, |builder, layer| layer.add_to(builder))?;
+            match self.conv_layers.last() {
+                Some(ConvLayerConfig::GlobalAvgPool) => builder,
+                _ => builder.flatten(),
+            }
+        };
+        for (i, (((activation, weight_init), bias_init), &size)) in layers {
+            builder = builder.dense_with_init(size, activation, weight_init, bias_init);
+            if i == output_index {
+                continue;
+            }
+            builder = match self.normalization {
+                Normalization::None => builder,
+                Normalization::BatchNorm => builder.batch_norm(),
+                Normalization::LayerNorm => builder.layer_norm(),
+            };
+            if self.dropout > 0.0 {
+                builder = builder.dropout(self.dropout);
+            }
+        }
+        builder.build_with_rng(rng)
+    }
+
+    /// Number of values the convolution layers output once flattened,
+    /// checking that every layer fits its inputs.
+    pub fn conv_output_size(&self) -> Result<usize> {
+        let shape = self.conv_layers.iter().try_fold(self.input_shape, |shape, layer| layer.output_shape(shape))?;
+        Ok(shape_len(shape))
+    }
+
+    /// Checks that the configuration describes a network that can be trained.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: &str| Err(NeuralNetError::InvalidConfig(message.to_string()));
+        if self.layers.len() < 2 {
+            return invalid("at least two layers required (input and output)");
+        }
+        if self.layers.contains(&0) {
+            return invalid("every layer needs at least one neuron");
+        }
+        if self.activations.len() != self.layers.len() - 1 {
+            return Err(NeuralNetError::InvalidConfig(format!(
+                "{} layers need {} activations, got {}",
+                self.layers.len(),
+                self.layers.len() - 1,
+                self.activations.len()
+            )));
+        }
+        self.parse_activations()?;
+        if !self.conv_layers.is_empty() {
+            if shape_len(self.input_shape) == 0 {
+                return invalid("input_shape must not contain zeros");
+            }
+            let size = self.conv_output_size()?;
+            if self.layers[0] != size {
+                return Err(NeuralNetError::InvalidConfig(format!(
+                    "layers[0] must be {}, the number of values the convolution layers output, got {}",
+                    size, self.layers[0]
+                )));
+            }
+        }
+        for (name, initializers) in [("weight_init", &self.weight_init), ("bias_init", &self.bias_init)] {
+            if !initializers.is_empty() && initializers.len() != self.layers.len() - 1 {
+                return Err(NeuralNetError::InvalidConfig(format!(
+                    "{} needs one entry per dense layer ({}), got {}",
+                    name,
+                    self.layers.len() - 1,
+                    initializers.len()
+                )));
+            }
+        }
+        self.parse_weight_init()?;
+        self.parse_bias_init()?;
+        if self.epochs == 0 {
+            return invalid("epochs must be at least 1");
+        }
+        if self.batch_size == 0 {
+            return invalid("batch_size must be at least 1");
+        }
+        if self.threads == 0 {
+            return invalid("threads must be at least 1");
+        }
+        if self.threads > 1 && self.parallel_mode == ParallelMode::Hogwild && self.optimizer != OptimizerConfig::Sgd {
+            return invalid("Hogwild training only supports the SGD optimizer");
+        }
+        if !(self.learning_rate > 0.0 && self.learning_rate.is_finite()) {
+            return invalid("learning_rate must be a positive number");
+        }
+        if !(0.0..1.0).contains(&self.label_smoothing) {
+            return invalid("label_smoothing must be in [0, 1)");
+        }
+        if !(0.0..1.0).contains(&self.dropout) {
+            return invalid("dropout must be in [0, 1)");
+        }
+        let weighted_layers = self.layers.len() - 1
+            + self.conv_layers.iter().filter(|layer| matches!(layer, ConvLayerConfig::Conv2d { .. })).count();
+        for (name, coefficients) in [("l1", &self.l1), ("l2", &self.l2)] {
+            expand_coefficients(name, coefficients, weighted_layers)?;
+            if !coefficients.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+                return Err(NeuralNetError::InvalidConfig(format!("{} coefficients must be non-negative numbers", name)));
+            }
+        }
+        if !(self.weight_decay >= 0.0 && self.weight_decay.is_finite()) {
+            return invalid("weight_decay must be a non-negative number");
+        }
+        if matches!(self.optimizer, OptimizerConfig::AdamW { .. }) && self.weight_decay == 0.0 {
+            return invalid("AdamW needs a positive weight_decay; without one it is Adam");
+        }
+        for (name, limit) in [("clip_value", self.clip_value), ("clip_norm", self.clip_norm)] {
+            if !(limit >= 0.0 && limit.is_finite()) {
+                return Err(NeuralNetError::InvalidConfig(format!("{} must be a non-negative number", name)));
+            }
+        }
+        if let DivergencePolicy::ReduceLr { factor } = self.on_divergence {
+            if !(factor > 0.0 && factor < 1.0) {
+                return invalid("on_divergence factor must be in (0, 1)");
+            }
+        }
+        match self.validation_split {
+            ValidationSplit::Fraction { fraction } if !(fraction > 0.0 && fraction < 1.0) => {
+                return invalid("validation_split fraction must be in (0, 1)");
+            }
+            ValidationSplit::Count { count: 0 } => return invalid("validation_split count must be at least 1"),
+            _ => {}
+        }
+        if let Some(early_stopping) = &self.early_stopping {
+            if !(early_stopping.min_delta >= 0.0 && early_stopping.min_delta.is_finite()) {
+                return invalid("early_stopping min_delta must be a non-negative number");
+            }
+        }
+        if self.temperature_scaling {
+            if self.validation_split == ValidationSplit::None {
+                return invalid("temperature_scaling needs a validation_split");
+            }
+            if self.parse_activations()?.last() != Some(&Activation::Softmax) {
+                return invalid("temperature_scaling needs a softmax output layer");
+            }
+        }
+        if self.checkpoint_every > 0 && self.checkpoint_dir.trim().is_empty() {
+            return invalid("checkpoint_dir must be set when checkpoint_every is not 0");
+        }
+        Ok(())
+    }
+}
+
+/// One convolution or pooling layer of `Config::conv_layers`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConvLayerConfig {
+    Conv2d {
+        channels: usize,
+        kernel_size: usize,
+        #[serde(default = "default_one")]
+        stride: usize,
+        #[serde(default)]
+        padding: usize,
+        #[serde(default = "default_one")]
+        dilation: usize,
+        /// Element-wise activation, e.g. `relu`.
+        activation: String,
+    },
+    MaxPool2d { kernel_size: usize, stride: usize },
+    AvgPool2d { kernel_size: usize, stride: usize },
+    GlobalAvgPool,
+}
+
+fn default_one() -> usize {
+    1
+}
+
+impl ConvLayerConfig {
+    /// Every kind of layer with typical settings.
+    pub fn all() -> [ConvLayerConfig; 4] {
+        [
+            ConvLayerConfig::Conv2d {
+                channels: 8,
+                kernel_size: 3,
+                stride: 1,
+                padding: 1,
+                dilation: 1,
+                activation: "relu".to_string(),
+            },
+            ConvLayerConfig::MaxPool2d { kernel_size: 2, stride: 2 },
+            ConvLayerConfig::AvgPool2d { kernel_size: 2, stride: 2 },
+            ConvLayerConfig::GlobalAvgPool,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConvLayerConfig::Conv2d { .. } => "Conv2d",
+            ConvLayerConfig::MaxPool2d { .. } => "Max Pool",
+            ConvLayerConfig::AvgPool2d { .. } => "Avg Pool",
+            ConvLayerConfig::GlobalAvgPool => "Global Avg Pool",
+        }
+    }
+
+    fn window(&self) -> Option<Window> {
+        match *self {
+            ConvLayerConfig::Conv2d { kernel_size, stride, padding, dilation, .. } => {
+                Some(Window::new(kernel_size).stride(stride).padding(padding).dilation(dilation))
+            }
+            ConvLayerConfig::MaxPool2d { kernel_size, stride } | ConvLayerConfig::AvgPool2d { kernel_size, stride } => {
+                Some(Window::new(kernel_size).stride(stride))
+            }
+            ConvLayerConfig::GlobalAvgPool => None,
+        }
+    }
+
+    /// Shape of this layer's outputs for inputs of `shape`, or an error if
+    /// the layer does not fit them.
+    pub fn output_shape(&self, (channels, height, width): ImageShape) -> Result<ImageShape> {
+        let (out_channels, activation) = match self {
+            ConvLayerConfig::Conv2d { channels, activation, .. } => (*channels, Some(activation.parse::<Activation>()?)),
+            ConvLayerConfig::GlobalAvgPool => return Ok((channels, 1, 1)),
+            _ => (channels, None),
+        };
+        if out_channels == 0 {
+            return Err(NeuralNetError::InvalidConfig("conv2d needs at least one channel".to_string()));
+        }
+        if let Some(activation @ (Activation::Softmax | Activation::PReLU(_))) = activation {
+            return Err(NeuralNetError::InvalidConfig(format!(
+                "conv2d needs an element-wise activation, got {:?}",
+                activation
+            )));
+        }
+        let (out_h, out_w) = self
+            .window()
+            .and_then(|window| window.output_dims(height, width))
+            .ok_or_else(|| {
+                NeuralNetError::InvalidConfig(format!("{:?} does not fit a {}x{} input", self, height, width))
+            })?;
+        Ok((out_channels, out_h, out_w))
+    }
+
+    fn add_to(&self, builder: NetworkBuilder) -> Result<NetworkBuilder> {
+        Ok(match self {
+            ConvLayerConfig::Conv2d { channels, kernel_size, stride, padding, dilation, activation } => {
+                let window = Window::new(*kernel_size).stride(*stride).padding(*padding).dilation(*dilation);
+                builder.conv2d(*channels, window, activation.parse()?)
+            }
+            ConvLayerConfig::MaxPool2d { kernel_size, stride } => builder.max_pool2d(*kernel_size, *stride),
+            ConvLayerConfig::AvgPool2d { kernel_size, stride } => builder.avg_pool2d(*kernel_size, *stride),
+            ConvLayerConfig::GlobalAvgPool => builder.global_avg_pool(),
+        })
+    }
+}
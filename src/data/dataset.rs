@@ -1,15 +1,30 @@
 
-use ndarray::Array1;
+use ndarray::{Array1, Array2, Array3};
+
+/// `(channels, height, width)` of an MNIST image.
+pub const MNIST_SHAPE: (usize, usize, usize) = (1, 28, 28);
 
 #[derive(Clone, Debug)]
 pub struct Sample {
-    pub inputs: Array1<f32>,  
+    /// Values with shape `(channels, height, width)`.
+    pub inputs: Array3<f32>,  
     pub target: Array1<f32>,  
 }
 
+impl Sample {
+    /// Index of the largest target value, i.e. the class of a one-hot target.
+    pub fn label(&self) -> usize {
+        self.target
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(idx, _)| idx)
+    }
+}
+
 pub fn create_samples(images: &[u8], labels: &[u8], num_classes: usize) -> Vec<Sample> {
     images
-        .chunks(784) // 28x28
+        .chunks(MNIST_SHAPE.0 * MNIST_SHAPE.1 * MNIST_SHAPE.2)
         .zip(labels.iter())
         .map(|(img, &lab)| Sample {
             inputs: normalize_images(img),
@@ -18,8 +33,23 @@ pub fn create_samples(images: &[u8], labels: &[u8], num_classes: usize) -> Vec<S
         .collect()
 }
 
-fn normalize_images(image: &[u8]) -> Array1<f32> {
-    Array1::from_iter(image.iter().map(|&p| p as f32 / 255.0))
+/// Stacks the given samples into `(inputs, targets)` matrices with one sample
+/// per row. Inputs are flattened in row-major order.
+pub fn to_batch(samples: &[&Sample]) -> (Array2<f32>, Array2<f32>) {
+    let input_len = samples.first().map_or(0, |s| s.inputs.len());
+    let target_len = samples.first().map_or(0, |s| s.target.len());
+    let mut inputs = Array2::zeros((samples.len(), input_len));
+    let mut targets = Array2::zeros((samples.len(), target_len));
+    for (i, sample) in samples.iter().enumerate() {
+        inputs.row_mut(i).iter_mut().zip(sample.inputs.iter()).for_each(|(row, &value)| *row = value);
+        targets.row_mut(i).assign(&sample.target);
+    }
+    (inputs, targets)
+}
+
+fn normalize_images(image: &[u8]) -> Array3<f32> {
+    let (_, height, width) = MNIST_SHAPE;
+    Array3::from_shape_fn(MNIST_SHAPE, |(c, y, x)| image[(c * height + y) * width + x] as f32 / 255.0)
 }
 
 fn one_hot_encode(label: u8, num_classes: usize) -> Array1<f32> {
@@ -31,3 +61,13 @@ fn one_hot_encode(label: u8, num_classes: usize) -> Array1<f32> {
 }
 
 
+/// Random images with labels cycling through the classes, for tests that
+/// train without the MNIST files.
+#[cfg(test)]
+pub(crate) fn random_samples(count: usize, seed: u64) -> Vec<Sample> {
+    use rand::Rng;
+    let mut rng = crate::utils::math::seeded_rng(Some(seed));
+    let images: Vec<u8> = (0..count * MNIST_SHAPE.1 * MNIST_SHAPE.2).map(|_| rng.gen()).collect();
+    let labels: Vec<u8> = (0..count).map(|i| (i % 10) as u8).collect();
+    create_samples(&images, &labels, 10)
+}
@@ -1,19 +1,320 @@
 
 use crate::data::dataset;
-use mnist::MnistBuilder;
+use flate2::read::GzDecoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 
-pub fn load_mnist() -> (Vec<dataset::Sample>, Vec<dataset::Sample>) {
-    let mnist = MnistBuilder::new()
-        .label_format_digit()
-        .training_set_length(50_000)
-        .validation_set_length(0)
-        .test_set_length(10_000)
-        .download_and_extract()
-        .finalize();
+pub const MNIST_DIR: &str = "data";
+pub const TRAIN_SET_LENGTH: usize = 60_000;
+pub const TEST_SET_LENGTH: usize = 10_000;
 
-    let train_set = dataset::create_samples(&mnist.trn_img, &mnist.trn_lbl, 10);
-    let test_set = dataset::create_samples(&mnist.tst_img, &mnist.tst_lbl, 10);
+const IDX1_MAGIC: u32 = 0x0000_0801;
+const IDX3_MAGIC: u32 = 0x0000_0803;
+const IMAGE_ROWS: usize = 28;
+const IMAGE_COLS: usize = 28;
 
-    (train_set, test_set)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdxSet {
+    Train,
+    Test,
 }
 
+impl IdxSet {
+    fn file_stems(&self) -> (&'static str, &'static str) {
+        match self {
+            IdxSet::Train => ("train-images-idx3-ubyte", "train-labels-idx1-ubyte"),
+            IdxSet::Test => ("t10k-images-idx3-ubyte", "t10k-labels-idx1-ubyte"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IdxError {
+    Missing(PathBuf),
+    Io { path: PathBuf, source: io::Error },
+    BadMagic { path: PathBuf, expected: u32, found: u32 },
+    BadDimensions { path: PathBuf, rows: usize, cols: usize },
+    Truncated { path: PathBuf, expected: usize, found: usize },
+    CountMismatch { images: usize, labels: usize },
+    TooFewSamples { path: PathBuf, requested: usize, available: usize },
+}
+
+impl fmt::Display for IdxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdxError::Missing(path) => write!(f, "IDX file not found: {}", path.display()),
+            IdxError::Io { path, source } => write!(f, "failed to read {}: {}", path.display(), source),
+            IdxError::BadMagic { path, expected, found } => write!(
+                f,
+                "{}: bad magic number {:#010x} (expected {:#010x})",
+                path.display(),
+                found,
+                expected
+            ),
+            IdxError::BadDimensions { path, rows, cols } => write!(
+                f,
+                "{}: images are {}x{}, expected {}x{}",
+                path.display(),
+                rows,
+                cols,
+                IMAGE_ROWS,
+                IMAGE_COLS
+            ),
+            IdxError::Truncated { path, expected, found } => write!(
+                f,
+                "{}: expected {} bytes of data, found {}",
+                path.display(),
+                expected,
+                found
+            ),
+            IdxError::CountMismatch { images, labels } => {
+                write!(f, "{} images but {} labels", images, labels)
+            }
+            IdxError::TooFewSamples { path, requested, available } => write!(
+                f,
+                "{}: requested {} samples but only {} available",
+                path.display(),
+                requested,
+                available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IdxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IdxError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Loads the MNIST train and test sets from the IDX files in `data/`.
+pub fn load_mnist() -> Result<(Vec<dataset::Sample>, Vec<dataset::Sample>), IdxError> {
+    load_mnist_from_dir(MNIST_DIR)
+}
+
+/// Loads the MNIST train and test sets from `dir`. Fails with
+/// `IdxError::Missing` when a file is absent: nothing is downloaded.
+pub fn load_mnist_from_dir<P: AsRef<Path>>(
+    dir: P,
+) -> Result<(Vec<dataset::Sample>, Vec<dataset::Sample>), IdxError> {
+    let dir = dir.as_ref();
+    let train_set = load_idx_set(dir, IdxSet::Train, TRAIN_SET_LENGTH)?;
+    let test_set = load_idx_set(dir, IdxSet::Test, TEST_SET_LENGTH)?;
+    Ok((train_set, test_set))
+}
+
+/// Reads the first `length` samples of an IDX image/label pair from `dir`.
+/// Each file may be stored raw or gzipped (`.gz`).
+pub fn load_idx_set(dir: &Path, set: IdxSet, length: usize) -> Result<Vec<dataset::Sample>, IdxError> {
+    let (image_stem, label_stem) = set.file_stems();
+    let image_path = resolve_idx_path(dir, image_stem)?;
+    let label_path = resolve_idx_path(dir, label_stem)?;
+
+    let images = read_idx3(&image_path)?;
+    let labels = read_idx1(&label_path)?;
+
+    let image_count = images.len() / (IMAGE_ROWS * IMAGE_COLS);
+    if image_count != labels.len() {
+        return Err(IdxError::CountMismatch { images: image_count, labels: labels.len() });
+    }
+    if length > image_count {
+        return Err(IdxError::TooFewSamples { path: image_path, requested: length, available: image_count });
+    }
+
+    Ok(dataset::create_samples(
+        &images[..length * IMAGE_ROWS * IMAGE_COLS],
+        &labels[..length],
+        10,
+    ))
+}
+
+/// Parses an idx1 (label) file and returns one byte per item.
+pub fn read_idx1(path: &Path) -> Result<Vec<u8>, IdxError> {
+    let bytes = read_maybe_gzipped(path)?;
+    let (magic, header) = read_header(path, &bytes, 1)?;
+    if magic != IDX1_MAGIC {
+        return Err(IdxError::BadMagic { path: path.to_path_buf(), expected: IDX1_MAGIC, found: magic });
+    }
+    let count = header[0];
+    take_payload(path, &bytes, 8, count)
+}
+
+/// Parses an idx3 (image) file and returns the pixels of every image, row-major.
+/// Images must be 28x28.
+pub fn read_idx3(path: &Path) -> Result<Vec<u8>, IdxError> {
+    let bytes = read_maybe_gzipped(path)?;
+    let (magic, header) = read_header(path, &bytes, 3)?;
+    if magic != IDX3_MAGIC {
+        return Err(IdxError::BadMagic { path: path.to_path_buf(), expected: IDX3_MAGIC, found: magic });
+    }
+    let (count, rows, cols) = (header[0], header[1], header[2]);
+    if rows != IMAGE_ROWS || cols != IMAGE_COLS {
+        return Err(IdxError::BadDimensions { path: path.to_path_buf(), rows, cols });
+    }
+    // a count too large to address cannot be present, so it reads as truncated
+    take_payload(path, &bytes, 16, count.saturating_mul(rows * cols))
+}
+
+fn resolve_idx_path(dir: &Path, stem: &str) -> Result<PathBuf, IdxError> {
+    let raw = dir.join(stem);
+    if raw.is_file() {
+        return Ok(raw);
+    }
+    let gz = dir.join(format!("{}.gz", stem));
+    if gz.is_file() {
+        return Ok(gz);
+    }
+    Err(IdxError::Missing(raw))
+}
+
+fn read_maybe_gzipped(path: &Path) -> Result<Vec<u8>, IdxError> {
+    let io_err = |source| IdxError::Io { path: path.to_path_buf(), source };
+    let mut file = File::open(path).map_err(io_err)?;
+    let mut bytes = Vec::new();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        GzDecoder::new(file).read_to_end(&mut bytes).map_err(io_err)?;
+    } else {
+        file.read_to_end(&mut bytes).map_err(io_err)?;
+    }
+    Ok(bytes)
+}
+
+/// Returns the magic number and the `dims` big-endian dimension sizes that follow it.
+fn read_header(path: &Path, bytes: &[u8], dims: usize) -> Result<(u32, Vec<usize>), IdxError> {
+    let header_len = 4 * (dims + 1);
+    if bytes.len() < header_len {
+        return Err(IdxError::Truncated { path: path.to_path_buf(), expected: header_len, found: bytes.len() });
+    }
+    let words: Vec<u32> = bytes[..header_len]
+        .chunks_exact(4)
+        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+        .collect();
+    Ok((words[0], words[1..].iter().map(|&d| d as usize).collect()))
+}
+
+fn take_payload(path: &Path, bytes: &[u8], offset: usize, len: usize) -> Result<Vec<u8>, IdxError> {
+    let found = bytes.len() - offset;
+    if found < len {
+        return Err(IdxError::Truncated { path: path.to_path_buf(), expected: len, found });
+    }
+    Ok(bytes[offset..offset + len].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// A fresh, empty directory for one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("neural_net_loader_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn idx_bytes(magic: u32, dims: &[u32], payload: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = std::iter::once(magic).chain(dims.iter().copied()).flat_map(u32::to_be_bytes).collect();
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn images(count: usize) -> Vec<u8> {
+        let pixels: Vec<u8> = (0..count * IMAGE_ROWS * IMAGE_COLS).map(|i| (i % 251) as u8).collect();
+        idx_bytes(IDX3_MAGIC, &[count as u32, IMAGE_ROWS as u32, IMAGE_COLS as u32], &pixels)
+    }
+
+    fn labels(count: usize) -> Vec<u8> {
+        let labels: Vec<u8> = (0..count).map(|i| (i % 10) as u8).collect();
+        idx_bytes(IDX1_MAGIC, &[count as u32], &labels)
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn empty_directory_is_missing() {
+        let dir = temp_dir("empty");
+        assert!(matches!(load_mnist_from_dir(&dir), Err(IdxError::Missing(_))));
+    }
+
+    #[test]
+    fn reads_raw_and_gzipped_files() {
+        let dir = temp_dir("gzip");
+        std::fs::write(dir.join("t10k-images-idx3-ubyte"), images(3)).unwrap();
+        std::fs::write(dir.join("t10k-labels-idx1-ubyte.gz"), gzip(&labels(3))).unwrap();
+        let samples = load_idx_set(&dir, IdxSet::Test, 2).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].label(), 1);
+
+        let raw = dir.join("images");
+        let gzipped = dir.join("images.gz");
+        std::fs::write(&raw, images(2)).unwrap();
+        std::fs::write(&gzipped, gzip(&images(2))).unwrap();
+        assert_eq!(read_idx3(&raw).unwrap(), read_idx3(&gzipped).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let dir = temp_dir("magic");
+        let path = dir.join("labels");
+        std::fs::write(&path, idx_bytes(IDX3_MAGIC, &[1], &[0])).unwrap();
+        assert!(matches!(read_idx1(&path), Err(IdxError::BadMagic { found: IDX3_MAGIC, .. })));
+    }
+
+    #[test]
+    fn rejects_bad_dimensions() {
+        let dir = temp_dir("dimensions");
+        let path = dir.join("images");
+        std::fs::write(&path, idx_bytes(IDX3_MAGIC, &[1, 2, 2], &[0; 4])).unwrap();
+        assert!(matches!(read_idx3(&path), Err(IdxError::BadDimensions { rows: 2, cols: 2, .. })));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let dir = temp_dir("truncated");
+        let header = dir.join("header");
+        std::fs::write(&header, &labels(1)[..6]).unwrap();
+        assert!(matches!(read_idx1(&header), Err(IdxError::Truncated { expected: 8, found: 6, .. })));
+
+        let payload = dir.join("payload");
+        let mut bytes = images(2);
+        bytes.pop();
+        std::fs::write(&payload, bytes).unwrap();
+        assert!(matches!(read_idx3(&payload), Err(IdxError::Truncated { expected: 1568, found: 1567, .. })));
+
+        let gzipped = dir.join("payload.gz");
+        let compressed = gzip(&labels(4));
+        std::fs::write(&gzipped, &compressed[..compressed.len() / 2]).unwrap();
+        assert!(matches!(read_idx1(&gzipped), Err(IdxError::Io { .. })));
+    }
+
+    #[test]
+    fn rejects_impossible_counts() {
+        let dir = temp_dir("counts_overflow");
+        let path = dir.join("images");
+        std::fs::write(&path, idx_bytes(IDX3_MAGIC, &[u32::MAX, IMAGE_ROWS as u32, IMAGE_COLS as u32], &[0; 784])).unwrap();
+        assert!(matches!(read_idx3(&path), Err(IdxError::Truncated { found: 784, .. })));
+    }
+
+    #[test]
+    fn rejects_mismatched_counts() {
+        let dir = temp_dir("counts");
+        std::fs::write(dir.join("train-images-idx3-ubyte"), images(3)).unwrap();
+        std::fs::write(dir.join("train-labels-idx1-ubyte"), labels(2)).unwrap();
+        assert!(matches!(
+            load_idx_set(&dir, IdxSet::Train, 2),
+            Err(IdxError::CountMismatch { images: 3, labels: 2 })
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::data::dataset::Sample;
+use crate::error::{NeuralNetError, Result};
+use serde::{Deserialize, Serialize};
+
+/// How many training samples to hold out for validation, as stored in `Config`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ValidationSplit {
+    None,
+    /// A share of the training samples, in `(0, 1)`.
+    Fraction { fraction: f32 },
+    /// A fixed number of samples.
+    Count { count: usize },
+}
+
+/// MNIST's usual split of 50k training and 10k validation images.
+impl Default for ValidationSplit {
+    fn default() -> Self {
+        ValidationSplit::Count { count: 10_000 }
+    }
+}
+
+impl ValidationSplit {
+    pub const ALL: [ValidationSplit; 3] = [
+        ValidationSplit::None,
+        ValidationSplit::Fraction { fraction: 0.1 },
+        ValidationSplit::Count { count: 10_000 },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValidationSplit::None => "None",
+            ValidationSplit::Fraction { .. } => "Fraction",
+            ValidationSplit::Count { .. } => "Count",
+        }
+    }
+
+    /// Number of samples held out of `total`.
+    pub fn count(&self, total: usize) -> usize {
+        match *self {
+            ValidationSplit::None => 0,
+            ValidationSplit::Fraction { fraction } => ((fraction * total as f32).round() as usize).max(1),
+            ValidationSplit::Count { count } => count,
+        }
+    }
+}
+
+/// Splits `samples` into training and validation sets, keeping the original
+/// order in both. The validation set is the last samples, or with
+/// `stratified` the last samples of every class in proportion to its size,
+/// so the same data always gives the same split.
+pub fn split_validation(samples: Vec<Sample>, split: ValidationSplit, stratified: bool) -> Result<(Vec<Sample>, Vec<Sample>)> {
+    let total = samples.len();
+    let count = split.count(total);
+    if count == 0 {
+        return Ok((samples, Vec::new()));
+    }
+    if count >= total {
+        return Err(NeuralNetError::InvalidConfig(format!(
+            "a validation split of {} leaves no training samples out of {}",
+            count, total
+        )));
+    }
+
+    let mut held_out = vec![false; total];
+    if stratified {
+        let mut classes: Vec<Vec<usize>> = Vec::new();
+        for (i, sample) in samples.iter().enumerate() {
+            let label = sample.label();
+            if classes.len() <= label {
+                classes.resize_with(label + 1, Vec::new);
+            }
+            classes[label].push(i);
+        }
+        for (indices, quota) in classes.iter().zip(class_quotas(&classes, count, total)) {
+            for &i in &indices[indices.len() - quota..] {
+                held_out[i] = true;
+            }
+        }
+    } else {
+        held_out[total - count..].fill(true);
+    }
+
+    let (validation, training): (Vec<_>, Vec<_>) =
+        samples.into_iter().zip(held_out).partition(|(_, held_out)| *held_out);
+    Ok((
+        training.into_iter().map(|(sample, _)| sample).collect(),
+        validation.into_iter().map(|(sample, _)| sample).collect(),
+    ))
+}
+
+/// Shares `count` among the classes in proportion to their sizes, giving the
+/// rounding remainder to the classes with the largest fractional shares.
+fn class_quotas(classes: &[Vec<usize>], count: usize, total: usize) -> Vec<usize> {
+    let shares: Vec<f64> = classes.iter().map(|c| c.len() as f64 * count as f64 / total as f64).collect();
+    let mut quotas: Vec<usize> = shares.iter().map(|share| share.floor() as usize).collect();
+    let mut by_remainder: Vec<usize> = (0..classes.len()).collect();
+    by_remainder.sort_by(|&a, &b| (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor())));
+    let missing = count - quotas.iter().sum::<usize>();
+    for &class in by_remainder.iter().take(missing) {
+        quotas[class] += 1;
+    }
+    quotas
+}
@@ -0,0 +1,86 @@
+use crate::data::loader::IdxError;
+use crate::network::activation::ParseActivationError;
+use crate::network::initializer::ParseInitializerError;
+use crate::training::stability::Divergence;
+use std::fmt;
+use std::io;
+
+/// Errors returned by the library. Nothing in `neural_net` panics on bad
+/// configuration, mismatched shapes or unreadable files; it returns one of these.
+#[derive(Debug)]
+pub enum NeuralNetError {
+    /// The configuration cannot describe a trainable network.
+    InvalidConfig(String),
+    /// Two sizes that have to agree do not, e.g. a sample's input length and
+    /// the input layer's size.
+    ShapeMismatch { context: String, expected: usize, found: usize },
+    Io(io::Error),
+    Serialization(serde_json::Error),
+    Dataset(IdxError),
+    /// Training hit NaN or infinity under `DivergencePolicy::Halt`.
+    Diverged(Divergence),
+}
+
+pub type Result<T> = std::result::Result<T, NeuralNetError>;
+
+impl NeuralNetError {
+    pub fn shape_mismatch(context: impl Into<String>, expected: usize, found: usize) -> Self {
+        NeuralNetError::ShapeMismatch { context: context.into(), expected, found }
+    }
+}
+
+impl fmt::Display for NeuralNetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NeuralNetError::InvalidConfig(message) => write!(f, "invalid configuration: {}", message),
+            NeuralNetError::ShapeMismatch { context, expected, found } => {
+                write!(f, "{}: expected {}, found {}", context, expected, found)
+            }
+            NeuralNetError::Io(e) => write!(f, "I/O error: {}", e),
+            NeuralNetError::Serialization(e) => write!(f, "serialization error: {}", e),
+            NeuralNetError::Dataset(e) => write!(f, "dataset error: {}", e),
+            NeuralNetError::Diverged(divergence) => write!(f, "training diverged: {}", divergence),
+        }
+    }
+}
+
+impl std::error::Error for NeuralNetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NeuralNetError::Io(e) => Some(e),
+            NeuralNetError::Serialization(e) => Some(e),
+            NeuralNetError::Dataset(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NeuralNetError {
+    fn from(e: io::Error) -> Self {
+        NeuralNetError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for NeuralNetError {
+    fn from(e: serde_json::Error) -> Self {
+        NeuralNetError::Serialization(e)
+    }
+}
+
+impl From<IdxError> for NeuralNetError {
+    fn from(e: IdxError) -> Self {
+        NeuralNetError::Dataset(e)
+    }
+}
+
+impl From<ParseActivationError> for NeuralNetError {
+    fn from(e: ParseActivationError) -> Self {
+        NeuralNetError::InvalidConfig(e.to_string())
+    }
+}
+
+impl From<ParseInitializerError> for NeuralNetError {
+    fn from(e: ParseInitializerError) -> Self {
+        NeuralNetError::InvalidConfig(e.to_string())
+    }
+}
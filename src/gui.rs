@@ -1,17 +1,35 @@
 use eframe::egui;
-use crate::config::Config;
+use crate::config::{Config, ConvLayerConfig};
 use crate::data::loader::load_mnist;
-use crate::network::initialize_network;
-use crate::network::layer::Layer;
+use crate::network::Network;
 use crate::network::activation::Activation;
-use crate::training::trainer::{train, forward_pass};
+use crate::network::initializer::Initializer;
+use crate::network::normalization::Normalization;
+use crate::training::trainer::train_epoch;
+use crate::training::optimizer::{OptimizerConfig, OptimizerState, ADAMW_WEIGHT_DECAY};
+use crate::training::scheduler::{LrScheduler, SchedulerConfig};
+use crate::training::loss::LossConfig;
+use crate::training::early_stopping::{EarlyStopping, EarlyStoppingConfig, Monitor};
+use crate::training::checkpoint::{
+    checkpoint_path, list_checkpoints, load_checkpoint, load_model, save_checkpoint, save_model, scheduled_checkpoint, Checkpoint,
+};
+use crate::training::regularization::Regularizer;
+use crate::training::stability::{DivergenceGuard, DivergencePolicy};
+use crate::training::parallel::ParallelMode;
 use crate::data::dataset::Sample;
-use crate::metrics::accuracy::evaluate;
+use crate::data::split::{split_validation, ValidationSplit};
+use crate::metrics::accuracy::evaluate_with_loss;
+use crate::metrics::calibration::{fit_temperature, CalibrationReport};
+use crate::metrics::confusion::{ClassMetrics, ConfusionMatrix};
+use crate::metrics::curves::OneVsRestCurves;
+use crate::metrics::predictions::Predictions;
+use crate::metrics::history::EpochMetrics;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub enum TrainingState {
@@ -21,20 +39,86 @@ pub enum TrainingState {
     Complete,
 }
 
+fn default_model_path() -> String {
+    "trained_model.json".to_string()
+}
+
+/// Curves shown by the ROC and precision-recall panel.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum CurveSelection {
+    #[default]
+    Micro,
+    Macro,
+    Class(usize),
+}
+
+impl CurveSelection {
+    fn name(self) -> String {
+        match self {
+            CurveSelection::Micro => "Micro average".to_string(),
+            CurveSelection::Macro => "Macro average".to_string(),
+            CurveSelection::Class(class) => format!("Digit {}", class),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct AppState {
     pub config: Config,
     pub progress: f32,
     pub train_accuracy: f32,
+    pub validation_accuracy: f32,
     pub test_accuracy: f32,
     pub status: String,
-    pub network: Option<Vec<crate::network::layer::Layer>>,
+    pub network: Option<Network>,
+    pub optimizer: Option<OptimizerState>,
+    pub continue_training: bool,
     pub train_accuracy_history: Vec<f32>,
-    pub test_accuracy_history: Vec<f32>,
+    pub validation_accuracy_history: Vec<f32>,
+    /// Test evaluations run on demand, as `(epoch, value)`.
+    pub test_accuracy_history: Vec<(usize, f32)>,
+    pub train_loss: f32,
+    pub validation_loss: f32,
+    pub test_loss: f32,
+    pub penalty: f32,
+    pub train_loss_history: Vec<f32>,
+    pub validation_loss_history: Vec<f32>,
+    pub test_loss_history: Vec<(usize, f32)>,
+    pub learning_rate: f32,
+    pub learning_rate_history: Vec<f32>,
     pub selected_sample_index: usize,
     pub prediction_result: Option<(usize, usize)>,
     pub needs_repaint: bool,
     pub training_state: TrainingState,
+    /// Checkpoint picked for Resume from Checkpoint.
+    pub resume_path: Option<PathBuf>,
+    /// File written by Save Model and read by Load Model.
+    #[serde(default = "default_model_path")]
+    pub model_path: String,
+    /// Confusion matrix cell whose test samples are listed, as `(actual, predicted)`.
+    pub selected_cell: Option<(usize, usize)>,
+    /// Softmax temperature fitted by temperature scaling.
+    pub temperature: Option<f32>,
+    /// Class or average whose ROC and PR curves are shown.
+    #[serde(default)]
+    pub curve_selection: CurveSelection,
+
+    /// Confusion matrix of the last test set evaluation.
+    #[serde(skip)]
+    pub confusion_matrix: Option<ConfusionMatrix>,
+
+    /// Calibration of the last test set evaluation, without and with the
+    /// fitted temperature.
+    #[serde(skip)]
+    pub test_calibration: Option<(CalibrationReport, Option<CalibrationReport>)>,
+
+    /// One-vs-rest curves of the last test set evaluation.
+    #[serde(skip)]
+    pub test_curves: Option<OneVsRestCurves>,
+
+    /// The run as of its last completed epoch, for Save Checkpoint.
+    #[serde(skip)]
+    pub checkpoint: Option<Checkpoint>,
 
     #[serde(skip)]
     pub texture_cache: HashMap<usize, egui::TextureHandle>,
@@ -54,11 +138,24 @@ impl Default for AppState {
             config: Config::default(),
             progress: 0.0,
             train_accuracy: 0.0,
+            validation_accuracy: 0.0,
             test_accuracy: 0.0,
             status: "Idle".to_string(),
             network: None,
+            optimizer: None,
+            continue_training: false,
             train_accuracy_history: Vec::new(),
+            validation_accuracy_history: Vec::new(),
             test_accuracy_history: Vec::new(),
+            train_loss: 0.0,
+            validation_loss: 0.0,
+            test_loss: 0.0,
+            penalty: 0.0,
+            train_loss_history: Vec::new(),
+            validation_loss_history: Vec::new(),
+            test_loss_history: Vec::new(),
+            learning_rate: 0.0,
+            learning_rate_history: Vec::new(),
             selected_sample_index: 0,
             prediction_result: None,
             needs_repaint: false,
@@ -66,6 +163,41 @@ impl Default for AppState {
             test_set: Vec::new(),
             train_set: Vec::new(),
             training_state: TrainingState::Idle,
+            resume_path: None,
+            model_path: default_model_path(),
+            selected_cell: None,
+            temperature: None,
+            curve_selection: CurveSelection::Micro,
+            confusion_matrix: None,
+            test_calibration: None,
+            test_curves: None,
+            checkpoint: None,
+        }
+    }
+}
+
+impl AppState {
+    /// Replaces the per-epoch histories with `history`, as at the start of a
+    /// run or when resuming one. Test evaluations are dropped.
+    fn set_history(&mut self, history: &[EpochMetrics]) {
+        self.train_accuracy_history = history.iter().map(|m| m.train_accuracy).collect();
+        self.validation_accuracy_history = history.iter().filter_map(|m| m.validation_accuracy).collect();
+        self.train_loss_history = history.iter().map(|m| m.train_loss).collect();
+        self.validation_loss_history = history.iter().filter_map(|m| m.validation_loss).collect();
+        self.learning_rate_history = history.iter().map(|m| m.learning_rate).collect();
+        self.test_accuracy_history.clear();
+        self.test_loss_history.clear();
+        self.confusion_matrix = None;
+        self.selected_cell = None;
+        self.test_calibration = None;
+        self.test_curves = None;
+        if let Some(last) = history.last() {
+            self.train_accuracy = last.train_accuracy;
+            self.train_loss = last.train_loss;
+            self.validation_accuracy = last.validation_accuracy.unwrap_or(0.0);
+            self.validation_loss = last.validation_loss.unwrap_or(0.0);
+            self.penalty = last.penalty;
+            self.learning_rate = last.learning_rate;
         }
     }
 }
@@ -76,14 +208,20 @@ pub struct GuiApp {
 
 impl Default for GuiApp {
     fn default() -> Self {
-        let (train_set, test_set) = load_mnist();
-
-        Self {
-            state: Arc::new(Mutex::new(AppState {
+        let state = match load_mnist() {
+            Ok((train_set, test_set)) => AppState {
                 train_set,
                 test_set,
                 ..AppState::default()
-            })),
+            },
+            Err(e) => AppState {
+                status: format!("Failed to load MNIST: {}", e),
+                ..AppState::default()
+            },
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
         }
     }
 }
@@ -103,6 +241,356 @@ impl GuiApp {
                 ui.add(egui::DragValue::new(&mut state.config.learning_rate).range(0.0001..=1.0));
             });
 
+            ui.horizontal(|ui| {
+                ui.label("Batch Size:");
+                ui.add(egui::DragValue::new(&mut state.config.batch_size).range(1..=512));
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Dropout:");
+                ui.add(egui::DragValue::new(&mut state.config.dropout).range(0.0..=0.9).speed(0.01));
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Normalization:");
+                let current = state.config.normalization;
+                egui::ComboBox::from_id_salt("normalization")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in Normalization::ALL {
+                            ui.selectable_value(&mut state.config.normalization, option, option.name());
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                let config = &mut state.config;
+                for (label, coefficients) in [("L1:", &mut config.l1), ("L2:", &mut config.l2)] {
+                    ui.label(label);
+                    if coefficients.len() > 1 {
+                        ui.label("per layer");
+                        continue;
+                    }
+                    let mut value = coefficients.first().copied().unwrap_or(0.0);
+                    if ui.add(egui::DragValue::new(&mut value).range(0.0..=1.0).speed(0.0001)).changed() {
+                        *coefficients = if value > 0.0 { vec![value] } else { Vec::new() };
+                    }
+                }
+                ui.label("Weight Decay:");
+                ui.add(egui::DragValue::new(&mut config.weight_decay).range(0.0..=1.0).speed(0.0001));
+                ui.checkbox(&mut config.regularize_all_parameters, "Include Biases");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Clip Value:");
+                ui.add(egui::DragValue::new(&mut state.config.clip_value).range(0.0..=100.0).speed(0.01));
+                ui.label("Clip Norm:");
+                ui.add(egui::DragValue::new(&mut state.config.clip_norm).range(0.0..=100.0).speed(0.01));
+
+                ui.label("On NaN/Inf:");
+                let current = state.config.on_divergence;
+                egui::ComboBox::from_id_salt("on_divergence")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in DivergencePolicy::ALL {
+                            let selected = std::mem::discriminant(&current) == std::mem::discriminant(&option);
+                            if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                                state.config.on_divergence = option;
+                            }
+                        }
+                    });
+                if let DivergencePolicy::ReduceLr { factor } = &mut state.config.on_divergence {
+                    ui.label("Factor:");
+                    ui.add(egui::DragValue::new(factor).range(0.01..=0.99).speed(0.01));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Threads:");
+                ui.add(egui::DragValue::new(&mut state.config.threads).range(1..=64));
+                ui.label("Parallel Mode:");
+                let current = state.config.parallel_mode;
+                egui::ComboBox::from_id_salt("parallel_mode")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in ParallelMode::ALL {
+                            ui.selectable_value(&mut state.config.parallel_mode, option, option.name());
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Validation Split:");
+                let current = state.config.validation_split;
+                egui::ComboBox::from_id_salt("validation_split")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in ValidationSplit::ALL {
+                            let selected = std::mem::discriminant(&current) == std::mem::discriminant(&option);
+                            if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                                state.config.validation_split = option;
+                            }
+                        }
+                    });
+
+                match &mut state.config.validation_split {
+                    ValidationSplit::None => {}
+                    ValidationSplit::Fraction { fraction } => {
+                        ui.add(egui::DragValue::new(fraction).range(0.01..=0.5).speed(0.01));
+                    }
+                    ValidationSplit::Count { count } => {
+                        ui.add(egui::DragValue::new(count).range(1..=30_000));
+                    }
+                }
+                ui.checkbox(&mut state.config.stratify_validation, "Stratified");
+                ui.checkbox(&mut state.config.temperature_scaling, "Temperature Scaling")
+                    .on_hover_text("Fit a softmax temperature on the validation set after training");
+            });
+
+            ui.horizontal(|ui| {
+                let mut enabled = state.config.early_stopping.is_some();
+                if ui.checkbox(&mut enabled, "Early Stopping").changed() {
+                    state.config.early_stopping = enabled.then(EarlyStoppingConfig::default);
+                }
+                if let Some(early_stopping) = state.config.early_stopping.as_mut() {
+                    ui.label("Monitor:");
+                    egui::ComboBox::from_id_salt("early_stopping_monitor")
+                        .selected_text(early_stopping.monitor.name())
+                        .show_ui(ui, |ui| {
+                            for option in Monitor::ALL {
+                                ui.selectable_value(&mut early_stopping.monitor, option, option.name());
+                            }
+                        });
+                    ui.label("Patience:");
+                    ui.add(egui::DragValue::new(&mut early_stopping.patience).range(0..=100));
+                    ui.label("Min Delta:");
+                    ui.add(egui::DragValue::new(&mut early_stopping.min_delta).range(0.0..=10.0).speed(0.001));
+                    ui.checkbox(&mut early_stopping.restore_best, "Restore Best");
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Checkpoint Every:");
+                ui.add(egui::DragValue::new(&mut state.config.checkpoint_every).range(0..=1000))
+                    .on_hover_text("Epochs between automatic checkpoints; 0 disables them");
+                ui.label("Directory:");
+                ui.text_edit_singleline(&mut state.config.checkpoint_dir);
+            });
+
+            ui.horizontal(|ui| {
+                let mut fixed_seed = state.config.seed.is_some();
+                if ui.checkbox(&mut fixed_seed, "Fixed Seed").changed() {
+                    state.config.seed = if fixed_seed { Some(0) } else { None };
+                }
+                if let Some(seed) = state.config.seed.as_mut() {
+                    ui.add(egui::DragValue::new(seed));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Optimizer:");
+                let current = state.config.optimizer;
+                egui::ComboBox::from_id_salt("optimizer")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in OptimizerConfig::ALL {
+                            let selected = std::mem::discriminant(&current) == std::mem::discriminant(&option);
+                            if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                                state.config.optimizer = option;
+                                if matches!(option, OptimizerConfig::AdamW { .. }) && state.config.weight_decay == 0.0 {
+                                    state.config.weight_decay = ADAMW_WEIGHT_DECAY;
+                                }
+                            }
+                        }
+                    });
+
+                match &mut state.config.optimizer {
+                    OptimizerConfig::Sgd => {}
+                    OptimizerConfig::Nesterov { momentum } => {
+                        ui.label("Momentum:");
+                        ui.add(egui::DragValue::new(momentum).range(0.0..=0.999).speed(0.01));
+                    }
+                    OptimizerConfig::AdaGrad { .. } => {}
+                    OptimizerConfig::RMSProp { decay, .. } => {
+                        ui.label("Decay:");
+                        ui.add(egui::DragValue::new(decay).range(0.0..=0.999).speed(0.01));
+                    }
+                    OptimizerConfig::Adam { beta1, beta2, .. } | OptimizerConfig::AdamW { beta1, beta2, .. } => {
+                        ui.label("Beta1:");
+                        ui.add(egui::DragValue::new(beta1).range(0.0..=0.999).speed(0.01));
+                        ui.label("Beta2:");
+                        ui.add(egui::DragValue::new(beta2).range(0.0..=0.9999).speed(0.001));
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Loss:");
+                let current = state.config.loss;
+                egui::ComboBox::from_id_salt("loss")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in LossConfig::ALL {
+                            let selected = std::mem::discriminant(&current) == std::mem::discriminant(&option);
+                            if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                                state.config.loss = option;
+                            }
+                        }
+                    });
+
+                match &mut state.config.loss {
+                    LossConfig::Huber { delta } => {
+                        ui.label("Delta:");
+                        ui.add(egui::DragValue::new(delta).range(0.01..=10.0).speed(0.01));
+                    }
+                    LossConfig::Hinge { margin } => {
+                        ui.label("Margin:");
+                        ui.add(egui::DragValue::new(margin).range(0.0..=10.0).speed(0.01));
+                    }
+                    LossConfig::Focal { gamma } => {
+                        ui.label("Gamma:");
+                        ui.add(egui::DragValue::new(gamma).range(0.0..=10.0).speed(0.1));
+                    }
+                    _ => {}
+                }
+
+                ui.label("Label Smoothing:");
+                ui.add(egui::DragValue::new(&mut state.config.label_smoothing).range(0.0..=0.5).speed(0.01));
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("LR Schedule:");
+                let current = state.config.scheduler;
+                egui::ComboBox::from_id_salt("scheduler")
+                    .selected_text(current.name())
+                    .show_ui(ui, |ui| {
+                        for option in SchedulerConfig::ALL {
+                            let selected = std::mem::discriminant(&current) == std::mem::discriminant(&option);
+                            if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                                state.config.scheduler = option;
+                            }
+                        }
+                    });
+
+                match &mut state.config.scheduler {
+                    SchedulerConfig::Constant => {}
+                    SchedulerConfig::StepDecay { step_size, gamma } => {
+                        ui.label("Step (epochs):");
+                        ui.add(egui::DragValue::new(step_size).range(1..=1000));
+                        ui.label("Gamma:");
+                        ui.add(egui::DragValue::new(gamma).range(0.0..=1.0).speed(0.01));
+                    }
+                    SchedulerConfig::Exponential { gamma } => {
+                        ui.label("Gamma:");
+                        ui.add(egui::DragValue::new(gamma).range(0.0..=1.0).speed(0.01));
+                    }
+                    SchedulerConfig::CosineWarmRestarts { period, period_mult, min_lr } => {
+                        ui.label("Period (epochs):");
+                        ui.add(egui::DragValue::new(period).range(1..=1000));
+                        ui.label("Period Mult:");
+                        ui.add(egui::DragValue::new(period_mult).range(1..=10));
+                        ui.label("Min LR:");
+                        ui.add(egui::DragValue::new(min_lr).range(0.0..=1.0).speed(0.0001));
+                    }
+                    SchedulerConfig::LinearWarmup { warmup_epochs } => {
+                        ui.label("Warmup (epochs):");
+                        ui.add(egui::DragValue::new(warmup_epochs).range(1..=1000));
+                    }
+                    SchedulerConfig::OneCycle { pct_start, div_factor, final_div_factor } => {
+                        ui.label("Warmup Fraction:");
+                        ui.add(egui::DragValue::new(pct_start).range(0.0..=1.0).speed(0.01));
+                        ui.label("Div Factor:");
+                        ui.add(egui::DragValue::new(div_factor).range(1.0..=1000.0));
+                        ui.label("Final Div Factor:");
+                        ui.add(egui::DragValue::new(final_div_factor).range(1.0..=1e6));
+                    }
+                    SchedulerConfig::ReduceOnPlateau { factor, patience, min_delta, .. } => {
+                        ui.label("Factor:");
+                        ui.add(egui::DragValue::new(factor).range(0.0..=1.0).speed(0.01));
+                        ui.label("Patience:");
+                        ui.add(egui::DragValue::new(patience).range(0..=100));
+                        ui.label("Min Delta (%):");
+                        ui.add(egui::DragValue::new(min_delta).range(0.0..=10.0).speed(0.01));
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Input Shape (C, H, W):");
+                ui.add(egui::DragValue::new(&mut state.config.input_shape.0).range(1..=64));
+                ui.add(egui::DragValue::new(&mut state.config.input_shape.1).range(1..=1024));
+                ui.add(egui::DragValue::new(&mut state.config.input_shape.2).range(1..=1024));
+            });
+
+            let mut removed = None;
+            for (i, layer) in state.config.conv_layers.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt(("conv_layer", i))
+                        .selected_text(layer.name())
+                        .show_ui(ui, |ui| {
+                            for option in ConvLayerConfig::all() {
+                                let selected = std::mem::discriminant(layer) == std::mem::discriminant(&option);
+                                if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                                    *layer = option;
+                                }
+                            }
+                        });
+
+                    match layer {
+                        ConvLayerConfig::Conv2d { channels, kernel_size, stride, padding, dilation, activation } => {
+                            ui.label("Channels:");
+                            ui.add(egui::DragValue::new(channels).range(1..=256));
+                            ui.label("Kernel:");
+                            ui.add(egui::DragValue::new(kernel_size).range(1..=11));
+                            ui.label("Stride:");
+                            ui.add(egui::DragValue::new(stride).range(1..=8));
+                            ui.label("Padding:");
+                            ui.add(egui::DragValue::new(padding).range(0..=8));
+                            ui.label("Dilation:");
+                            ui.add(egui::DragValue::new(dilation).range(1..=8));
+                            ui.label("Activation:");
+                            ui.add(egui::TextEdit::singleline(activation).desired_width(80.0));
+                        }
+                        ConvLayerConfig::MaxPool2d { kernel_size, stride }
+                        | ConvLayerConfig::AvgPool2d { kernel_size, stride } => {
+                            ui.label("Kernel:");
+                            ui.add(egui::DragValue::new(kernel_size).range(1..=8));
+                            ui.label("Stride:");
+                            ui.add(egui::DragValue::new(stride).range(1..=8));
+                        }
+                        ConvLayerConfig::GlobalAvgPool => {}
+                    }
+
+                    if ui.button("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = removed {
+                state.config.conv_layers.remove(i);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Add Conv Layer").clicked() {
+                    state.config.conv_layers.push(ConvLayerConfig::all()[0].clone());
+                }
+                if ui.button("LeNet-5").clicked() {
+                    let lenet = Config::lenet5();
+                    state.config.input_shape = lenet.input_shape;
+                    state.config.conv_layers = lenet.conv_layers;
+                    state.config.layers = lenet.layers;
+                    state.config.activations = lenet.activations;
+                    state.config.weight_init.clear();
+                    state.config.bias_init.clear();
+                }
+                if !state.config.conv_layers.is_empty() {
+                    match state.config.conv_output_size() {
+                        Ok(size) => ui.label(format!("Flattened size: {} (first layer size)", size)),
+                        Err(e) => ui.colored_label(egui::Color32::RED, format!("Error: {}.", e)),
+                    };
+                }
+            });
+
             let mut layers_input = state
                 .config
                 .layers
@@ -122,7 +610,7 @@ impl GuiApp {
 
             let mut activations_input = state.config.activations.join(",");
             if ui
-                .add(egui::TextEdit::singleline(&mut activations_input).hint_text("e.g., sigmoid,relu,relu"))
+                .add(egui::TextEdit::singleline(&mut activations_input).hint_text("e.g., sigmoid,relu,leaky_relu(0.1)"))
                 .changed()
             {
                 state.config.activations = activations_input
@@ -131,6 +619,39 @@ impl GuiApp {
                     .collect();
             }
 
+            for name in &state.config.activations {
+                if let Err(e) = name.parse::<Activation>() {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}.", e));
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Weight Init:");
+                let mut weight_init_input = state.config.weight_init.join(",");
+                if ui
+                    .add(egui::TextEdit::singleline(&mut weight_init_input).hint_text("auto, or e.g. he_normal,xavier_uniform"))
+                    .changed()
+                {
+                    state.config.weight_init = parse_list(&weight_init_input);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Bias Init:");
+                let mut bias_init_input = state.config.bias_init.join(",");
+                if ui
+                    .add(egui::TextEdit::singleline(&mut bias_init_input).hint_text("auto, or e.g. zeros,constant(0.1)"))
+                    .changed()
+                {
+                    state.config.bias_init = parse_list(&bias_init_input);
+                }
+            });
+
+            for name in state.config.weight_init.iter().chain(&state.config.bias_init) {
+                if let Err(e) = name.parse::<Initializer>() {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}.", e));
+                }
+            }
+
             if state.config.layers.len() < 2 {
                 ui.colored_label(egui::Color32::RED, "Error: At least two layers required (input and output).");
             }
@@ -141,10 +662,7 @@ impl GuiApp {
     }
 
     fn ui_training_controls(&self, ui: &mut egui::Ui) {
-        let (training_state, progress) = {
-            let lock = self.state.lock().unwrap();
-            (lock.training_state, lock.progress)
-        };
+        let training_state = self.state.lock().unwrap().training_state;
 
         ui.horizontal(|ui| {
             let start_enabled = matches!(training_state, TrainingState::Idle | TrainingState::Complete);
@@ -155,10 +673,9 @@ impl GuiApp {
                     let mut lock = state_clone.lock().unwrap();
                     lock.training_state = TrainingState::Training;
                     lock.status = "Training started".to_string();
-                    lock.train_accuracy_history.clear();
-                    lock.test_accuracy_history.clear();
+                    lock.set_history(&[]);
                 }
-                self.spawn_training_thread(state_clone);
+                self.spawn_training_thread(state_clone, None);
             }
 
             match training_state {
@@ -194,12 +711,21 @@ impl GuiApp {
                 }
             }
 
+            let can_evaluate = self.state.lock().unwrap().network.is_some();
+            if ui.add_enabled(can_evaluate, egui::Button::new("Evaluate Test Set")).clicked() {
+                self.spawn_test_evaluation(Arc::clone(&self.state));
+            }
+
+            ui.label("Model:");
+            ui.text_edit_singleline(&mut self.state.lock().unwrap().model_path);
+
             if ui.button("Save Model").clicked() {
                 let mut lock = self.state.lock().unwrap();
                 if let Some(ref network) = lock.network {
-                    let serialized = serde_json::to_string(network).unwrap();
-                    std::fs::write("trained_model.json", serialized).expect("Unable to save model");
-                    lock.status = "Model saved successfully.".to_string();
+                    lock.status = match save_model(&lock.model_path, network, lock.optimizer.as_ref(), lock.temperature) {
+                        Ok(()) => format!("Saved model to {}", lock.model_path),
+                        Err(e) => format!("Failed to save model to {}: {}", lock.model_path, e),
+                    };
                 } else {
                     lock.status = "No trained network to save.".to_string();
                 }
@@ -207,18 +733,76 @@ impl GuiApp {
 
             if ui.button("Load Model").clicked() {
                 let mut lock = self.state.lock().unwrap();
-                let data = std::fs::read_to_string("trained_model.json");
-                match data {
-                    Ok(content) => {
-                        let network: Vec<Layer> = serde_json::from_str(&content).unwrap();
+                match load_model(&lock.model_path) {
+                    Ok((network, optimizer, temperature)) => {
                         lock.network = Some(network);
-                        lock.status = "Model loaded successfully.".to_string();
+                        lock.optimizer = optimizer;
+                        lock.temperature = temperature;
+                        lock.status = format!("Loaded model from {}", lock.model_path);
                     }
-                    Err(_) => {
-                        lock.status = "Failed to load model.".to_string();
+                    Err(e) => {
+                        lock.status = format!("Failed to load model from {}: {}", lock.model_path, e);
                     }
                 }
             }
+
+            let mut lock = self.state.lock().unwrap();
+            let can_continue = lock.network.is_some();
+            ui.add_enabled(
+                can_continue,
+                egui::Checkbox::new(&mut lock.continue_training, "Continue from current model"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            let mut lock = self.state.lock().unwrap();
+            let start_enabled = matches!(training_state, TrainingState::Idle | TrainingState::Complete);
+
+            if ui.add_enabled(lock.checkpoint.is_some(), egui::Button::new("Save Checkpoint")).clicked() {
+                if let Some(checkpoint) = &lock.checkpoint {
+                    let path = checkpoint_path(&lock.config.checkpoint_dir, checkpoint.epoch);
+                    lock.status = match save_checkpoint(&path, checkpoint) {
+                        Ok(()) => format!("Saved checkpoint to {}", path.display()),
+                        Err(e) => format!("Failed to save checkpoint: {}", e),
+                    };
+                }
+            }
+
+            ui.label("Checkpoint:");
+            let selected_text = lock
+                .resume_path
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map_or("None".to_string(), |name| name.to_string_lossy().into_owned());
+            egui::ComboBox::from_id_salt("resume_checkpoint")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for path in list_checkpoints(&lock.config.checkpoint_dir) {
+                        let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                        ui.selectable_value(&mut lock.resume_path, Some(path), name);
+                    }
+                });
+
+            let can_resume = start_enabled && lock.resume_path.is_some();
+            if ui.add_enabled(can_resume, egui::Button::new("Resume from Checkpoint")).clicked() {
+                let Some(path) = lock.resume_path.clone() else {
+                    return;
+                };
+                match load_checkpoint(&path) {
+                    Ok(checkpoint) => {
+                        lock.config = checkpoint.config.clone();
+                        lock.set_history(&checkpoint.history);
+                        lock.network = Some(checkpoint.network.clone());
+                        lock.optimizer = Some(checkpoint.optimizer.clone());
+                        lock.progress = checkpoint.epoch as f32 / checkpoint.config.epochs as f32 * 100.0;
+                        lock.training_state = TrainingState::Training;
+                        lock.status = format!("Resuming from {}", path.display());
+                        drop(lock);
+                        self.spawn_training_thread(Arc::clone(&self.state), Some(checkpoint));
+                    }
+                    Err(e) => lock.status = format!("Failed to load checkpoint {}: {}", path.display(), e),
+                }
+            }
         });
     }
 
@@ -241,9 +825,19 @@ impl GuiApp {
     }
 
     fn ui_progress_and_accuracy(&self, ui: &mut egui::Ui) {
-        let (progress, train_acc, test_acc) = {
+        let (progress, train_acc, validation_acc, train_loss, validation_loss, test, penalty, learning_rate) = {
             let lock = self.state.lock().unwrap();
-            (lock.progress, lock.train_accuracy, lock.test_accuracy)
+            let test = (!lock.test_accuracy_history.is_empty()).then_some((lock.test_accuracy, lock.test_loss));
+            (
+                lock.progress,
+                lock.train_accuracy,
+                lock.validation_accuracy,
+                lock.train_loss,
+                lock.validation_loss,
+                test,
+                lock.penalty,
+                lock.learning_rate,
+            )
         };
 
         ui.add(egui::ProgressBar::new(progress / 100.0).show_percentage());
@@ -251,53 +845,362 @@ impl GuiApp {
 
         ui.horizontal(|ui| {
             ui.label(format!("Training Accuracy: {:.2}%", train_acc));
-            ui.label(format!("Testing Accuracy: {:.2}%", test_acc));
+            ui.label(format!("Validation Accuracy: {:.2}%", validation_acc));
+            match test {
+                Some((test_acc, _)) => ui.label(format!("Testing Accuracy: {:.2}%", test_acc)),
+                None => ui.label("Testing Accuracy: not evaluated"),
+            };
+        });
+        ui.horizontal(|ui| {
+            ui.label(format!("Training Loss: {:.4}", train_loss));
+            ui.label(format!("Validation Loss: {:.4}", validation_loss));
+            if let Some((_, test_loss)) = test {
+                ui.label(format!("Testing Loss: {:.4}", test_loss));
+            }
+            ui.label(format!("Penalty: {:.4}", penalty));
+            ui.label(format!("Learning Rate: {:.6}", learning_rate));
         });
     }
 
     fn ui_training_metrics(&self, ui: &mut egui::Ui) {
-        let (train_history, test_history) = {
+        let (train_history, validation_history, test_history, train_loss_history, validation_loss_history, test_loss_history, lr_history) = {
             let lock = self.state.lock().unwrap();
             (
                 lock.train_accuracy_history.clone(),
+                lock.validation_accuracy_history.clone(),
                 lock.test_accuracy_history.clone(),
+                lock.train_loss_history.clone(),
+                lock.validation_loss_history.clone(),
+                lock.test_loss_history.clone(),
+                lock.learning_rate_history.clone(),
             )
         };
+        let per_epoch = |history: &[f32]| -> Vec<[f64; 2]> {
+            history.iter().enumerate().map(|(i, &value)| [i as f64, value as f64]).collect()
+        };
+        let on_demand = |history: &[(usize, f32)]| -> Vec<[f64; 2]> {
+            history.iter().map(|&(epoch, value)| [epoch as f64, value as f64]).collect()
+        };
 
         ui.collapsing("Training Metrics", |ui| {
-            egui_plot::Plot::new("Accuracy Plot")
+            ui.columns(3, |columns| {
+                egui_plot::Plot::new("Accuracy Plot")
+                    .view_aspect(2.0)
+                    .legend(egui_plot::Legend::default())
+                    .show(&mut columns[0], |plot_ui| {
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from_iter(per_epoch(&train_history)))
+                                .name("Train Accuracy"),
+                        );
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from_iter(per_epoch(&validation_history)))
+                                .name("Validation Accuracy"),
+                        );
+                        plot_ui.points(
+                            egui_plot::Points::new(egui_plot::PlotPoints::from_iter(on_demand(&test_history)))
+                                .radius(4.0)
+                                .name("Test Accuracy"),
+                        );
+                    });
+
+                egui_plot::Plot::new("Loss Plot")
+                    .view_aspect(2.0)
+                    .legend(egui_plot::Legend::default())
+                    .show(&mut columns[1], |plot_ui| {
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from_iter(per_epoch(&train_loss_history)))
+                                .name("Train Loss"),
+                        );
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from_iter(per_epoch(&validation_loss_history)))
+                                .name("Validation Loss"),
+                        );
+                        plot_ui.points(
+                            egui_plot::Points::new(egui_plot::PlotPoints::from_iter(on_demand(&test_loss_history)))
+                                .radius(4.0)
+                                .name("Test Loss"),
+                        );
+                    });
+
+                egui_plot::Plot::new("Learning Rate Plot")
+                    .view_aspect(2.0)
+                    .show(&mut columns[2], |plot_ui| {
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from_iter(per_epoch(&lr_history)))
+                                .name("Learning Rate"),
+                        );
+                    });
+            });
+        });
+    }
+
+    /// The confusion matrix of the last test evaluation as a grid shaded by
+    /// the share of each actual class, with the per-class metrics. Clicking a
+    /// cell lists its test samples.
+    fn ui_confusion_matrix(&self, ui: &mut egui::Ui) {
+        ui.collapsing("Confusion Matrix", |ui| {
+            let mut lock = self.state.lock().unwrap();
+            let lock = &mut *lock;
+            let Some(matrix) = &lock.confusion_matrix else {
+                ui.label("Evaluate the test set to see its confusion matrix.");
+                return;
+            };
+            let classes = matrix.num_classes();
+
+            egui::Grid::new("confusion_matrix").spacing([2.0, 2.0]).show(ui, |ui| {
+                ui.label("actual \\ predicted");
+                for predicted in 0..classes {
+                    ui.label(predicted.to_string());
+                }
+                ui.end_row();
+                for actual in 0..classes {
+                    ui.label(actual.to_string());
+                    for predicted in 0..classes {
+                        let count = matrix.count(actual, predicted);
+                        let share = count as f32 / matrix.support(actual).max(1) as f32;
+                        let base = if actual == predicted {
+                            egui::Color32::from_rgb(40, 160, 70)
+                        } else {
+                            egui::Color32::from_rgb(200, 60, 60)
+                        };
+                        let fill = if count == 0 {
+                            egui::Color32::TRANSPARENT
+                        } else {
+                            base.gamma_multiply(0.15 + 0.85 * share)
+                        };
+                        let selected = lock.selected_cell == Some((actual, predicted));
+                        let button = egui::Button::new(count.to_string())
+                            .fill(fill)
+                            .selected(selected)
+                            .min_size(egui::vec2(40.0, 20.0));
+                        if ui.add(button).clicked() {
+                            lock.selected_cell = Some((actual, predicted));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some((actual, predicted)) = lock.selected_cell {
+                let samples = matrix.samples(actual, predicted);
+                ui.label(format!(
+                    "{} test samples of class {} predicted as {} (click one to show it under Make a Prediction):",
+                    samples.len(),
+                    actual,
+                    predicted
+                ));
+                egui::ScrollArea::vertical().id_salt("confusion_samples").max_height(80.0).show(ui, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        for &index in samples {
+                            if ui.link(index.to_string()).clicked() {
+                                lock.selected_sample_index = index;
+                                lock.prediction_result = None;
+                            }
+                        }
+                    });
+                });
+            }
+
+            let report = matrix.report();
+            let row = |ui: &mut egui::Ui, name: &str, metrics: &ClassMetrics| {
+                ui.label(name);
+                ui.label(format!("{:.4}", metrics.precision));
+                ui.label(format!("{:.4}", metrics.recall));
+                ui.label(format!("{:.4}", metrics.f1));
+                ui.label(metrics.support.to_string());
+                ui.end_row();
+            };
+            egui::Grid::new("class_metrics").striped(true).show(ui, |ui| {
+                for header in ["Class", "Precision", "Recall", "F1", "Support"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                for (class, metrics) in report.per_class.iter().enumerate() {
+                    row(ui, &class.to_string(), metrics);
+                }
+                row(ui, "Macro avg", &report.macro_average);
+                row(ui, "Weighted avg", &report.weighted_average);
+            });
+            ui.label(format!(
+                "Accuracy: {:.4} | Balanced accuracy: {:.4} | Cohen's kappa: {:.4}",
+                report.accuracy, report.balanced_accuracy, report.cohens_kappa
+            ));
+        });
+    }
+
+    /// Probabilistic metrics and the reliability diagram of the last test
+    /// evaluation, also with the fitted temperature when there is one.
+    fn ui_calibration(&self, ui: &mut egui::Ui) {
+        ui.collapsing("Calibration", |ui| {
+            let (calibration, temperature) = {
+                let lock = self.state.lock().unwrap();
+                (lock.test_calibration.clone(), lock.temperature)
+            };
+            let Some((report, scaled)) = calibration else {
+                ui.label("Evaluate the test set to see how well its probabilities are calibrated.");
+                return;
+            };
+            let reports: Vec<(String, &CalibrationReport)> = std::iter::once(("Uncalibrated".to_string(), &report))
+                .chain(scaled.iter().zip(temperature).map(|(scaled, t)| (format!("T = {:.4}", t), scaled)))
+                .collect();
+
+            egui::Grid::new("calibration_metrics").striped(true).show(ui, |ui| {
+                ui.strong("Metric");
+                for (name, _) in &reports {
+                    ui.strong(name);
+                }
+                ui.end_row();
+                for (i, (k, _)) in report.top_k_accuracy.iter().enumerate() {
+                    ui.label(format!("Top-{} accuracy", k));
+                    for (_, report) in &reports {
+                        ui.label(format!("{:.2}%", report.top_k_accuracy[i].1));
+                    }
+                    ui.end_row();
+                }
+                let values = |r: &CalibrationReport| {
+                    [r.negative_log_likelihood, r.brier_score, r.expected_calibration_error, r.maximum_calibration_error]
+                };
+                for (i, name) in ["Log loss (NLL)", "Brier score", "ECE", "MCE"].into_iter().enumerate() {
+                    ui.label(name);
+                    for (_, report) in &reports {
+                        ui.label(format!("{:.4}", values(report)[i]));
+                    }
+                    ui.end_row();
+                }
+            });
+
+            egui_plot::Plot::new("Reliability Diagram")
                 .view_aspect(2.0)
+                .include_x(0.0)
+                .include_x(1.0)
+                .include_y(0.0)
+                .include_y(1.0)
+                .x_axis_label("Confidence")
+                .y_axis_label("Accuracy")
+                .legend(egui_plot::Legend::default())
                 .show(ui, |plot_ui| {
-                    let train_data: Vec<[f64; 2]> = train_history
-                        .iter()
-                        .enumerate()
-                        .map(|(i, &acc)| [i as f64, acc as f64])
-                        .collect();
-
-                    let test_data: Vec<[f64; 2]> = test_history
-                        .iter()
-                        .enumerate()
-                        .map(|(i, &acc)| [i as f64, acc as f64])
-                        .collect();
-
                     plot_ui.line(
-                        egui_plot::Line::new(egui_plot::PlotPoints::from_iter(train_data))
-                            .name("Train Accuracy"),
-                    );
-                    plot_ui.line(
-                        egui_plot::Line::new(egui_plot::PlotPoints::from_iter(test_data))
-                            .name("Test Accuracy"),
+                        egui_plot::Line::new(egui_plot::PlotPoints::from(vec![[0.0, 0.0], [1.0, 1.0]]))
+                            .style(egui_plot::LineStyle::dashed_loose())
+                            .name("Perfect calibration"),
                     );
+                    for (name, report) in &reports {
+                        let bars = report
+                            .reliability
+                            .bins
+                            .iter()
+                            .filter(|bin| bin.count > 0)
+                            .map(|bin| {
+                                egui_plot::Bar::new(((bin.lower + bin.upper) / 2.0) as f64, bin.accuracy as f64)
+                                    .width((bin.upper - bin.lower) as f64 * 0.9)
+                            })
+                            .collect();
+                        plot_ui.bar_chart(egui_plot::BarChart::new(bars).name(name));
+                    }
                 });
         });
     }
 
+    /// One-vs-rest ROC and precision-recall curves of the last test
+    /// evaluation for the selected class or average.
+    fn ui_curves(&self, ui: &mut egui::Ui) {
+        ui.collapsing("ROC and Precision-Recall Curves", |ui| {
+            let mut lock = self.state.lock().unwrap();
+            let lock = &mut *lock;
+            let Some(curves) = &lock.test_curves else {
+                ui.label("Evaluate the test set to see its ROC and precision-recall curves.");
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.label("Class:");
+                egui::ComboBox::from_id_salt("curve_selection")
+                    .selected_text(lock.curve_selection.name())
+                    .show_ui(ui, |ui| {
+                        let selections = [CurveSelection::Micro, CurveSelection::Macro]
+                            .into_iter()
+                            .chain((0..curves.per_class.len()).map(CurveSelection::Class));
+                        for selection in selections {
+                            ui.selectable_value(&mut lock.curve_selection, selection, selection.name());
+                        }
+                    });
+            });
+            // The macro average has a ROC curve but no precision-recall curve.
+            let selected = match lock.curve_selection {
+                CurveSelection::Micro => Some(&curves.micro),
+                CurveSelection::Macro => None,
+                CurveSelection::Class(class) => Some(curves.per_class.get(class).unwrap_or(&curves.micro)),
+            };
+            let roc = selected.map_or(&curves.macro_roc, |selected| &selected.roc);
+            let summary = match selected {
+                Some(selected) => format!(
+                    "{}: ROC AUC {:.4}, average precision {:.4} ({} positives, {} negatives)",
+                    lock.curve_selection.name(),
+                    selected.roc.auc,
+                    selected.pr.average_precision,
+                    selected.positives,
+                    selected.negatives
+                ),
+                None => format!("Macro average: area under the averaged ROC curve {:.4}", roc.auc),
+            };
+            ui.label(format!(
+                "{} | macro ROC AUC {:.4}, macro AP {:.4} | micro ROC AUC {:.4}, micro AP {:.4}",
+                summary,
+                curves.macro_roc_auc,
+                curves.macro_average_precision,
+                curves.micro.roc.auc,
+                curves.micro.pr.average_precision
+            ));
+
+            let to_points = |points: &[(f32, f32)]| -> Vec<[f64; 2]> {
+                points.iter().map(|&(x, y)| [x as f64, y as f64]).collect()
+            };
+            ui.columns(2, |columns| {
+                egui_plot::Plot::new("ROC Plot")
+                    .view_aspect(1.5)
+                    .include_x(0.0)
+                    .include_x(1.0)
+                    .include_y(0.0)
+                    .include_y(1.0)
+                    .x_axis_label("False positive rate")
+                    .y_axis_label("True positive rate")
+                    .legend(egui_plot::Legend::default())
+                    .show(&mut columns[0], |plot_ui| {
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from(vec![[0.0, 0.0], [1.0, 1.0]]))
+                                .style(egui_plot::LineStyle::dashed_loose())
+                                .name("Chance"),
+                        );
+                        plot_ui.line(
+                            egui_plot::Line::new(egui_plot::PlotPoints::from(to_points(&roc.points)))
+                                .name(format!("ROC (AUC {:.4})", roc.auc)),
+                        );
+                    });
+
+                egui_plot::Plot::new("PR Plot")
+                    .view_aspect(1.5)
+                    .include_x(0.0)
+                    .include_x(1.0)
+                    .include_y(0.0)
+                    .include_y(1.0)
+                    .x_axis_label("Recall")
+                    .y_axis_label("Precision")
+                    .legend(egui_plot::Legend::default())
+                    .show(&mut columns[1], |plot_ui| {
+                        if let Some(selected) = selected {
+                            plot_ui.line(
+                                egui_plot::Line::new(egui_plot::PlotPoints::from(to_points(&selected.pr.points)))
+                                    .name(format!("PR (AP {:.4})", selected.pr.average_precision)),
+                            );
+                        }
+                    });
+            });
+        });
+    }
+
     fn ui_prediction(&self, ui: &mut egui::Ui) {
         ui.collapsing("Make a Prediction", |ui| {
-            let (network_exists, selected_index) = {
-                let lock = self.state.lock().unwrap();
-                (lock.network.is_some(), lock.selected_sample_index)
-            };
+            let network_exists = self.state.lock().unwrap().network.is_some();
 
             if network_exists {
                 let test_set = {
@@ -338,21 +1241,22 @@ impl GuiApp {
                         ui.image(&texture_id);
 
                         if ui.button("Predict").clicked() {
-                            let predicted_label = {
-                                let lock = self.state.lock().unwrap();
-                                let network = lock.network.as_ref().unwrap();
-                                predict(network, sample)
-                            };
-
                             let actual_label = sample
                                 .target
                                 .iter()
                                 .position(|&v| v == 1.0)
                                 .unwrap_or(0);
 
-                            {
-                                let mut lock = self.state.lock().unwrap();
-                                lock.prediction_result = Some((predicted_label, actual_label));
+                            let mut lock = self.state.lock().unwrap();
+                            match lock.network.as_ref().map(|network| network.predict(&sample.inputs)) {
+                                Some(Ok(predicted_label)) => {
+                                    lock.prediction_result = Some((predicted_label, actual_label));
+                                }
+                                Some(Err(e)) => {
+                                    lock.prediction_result = None;
+                                    lock.status = format!("Prediction failed: {}", e);
+                                }
+                                None => {}
                             }
                         }
 
@@ -376,6 +1280,15 @@ impl GuiApp {
         });
     }
 
+    fn ui_network_summary(&self, ui: &mut egui::Ui) {
+        let lock = self.state.lock().unwrap();
+        if let Some(ref network) = lock.network {
+            ui.collapsing("Network Summary", |ui| {
+                ui.monospace(network.summary());
+            });
+        }
+    }
+
     fn ui_logs(&self, ui: &mut egui::Ui) {
         let mut lock = self.state.lock().unwrap();
         ui.collapsing("Logs", |ui| {
@@ -383,31 +1296,96 @@ impl GuiApp {
         });
     }
 
-    fn spawn_training_thread(&self, state_clone: Arc<Mutex<AppState>>) {
+    /// Evaluates the current network on the test set in the background, so
+    /// test metrics are only ever computed when asked for.
+    fn spawn_test_evaluation(&self, state_clone: Arc<Mutex<AppState>>) {
+        let (network, test_set, config, temperature) = {
+            let mut lock = state_clone.lock().unwrap();
+            let Some(network) = lock.network.clone() else {
+                return;
+            };
+            lock.status = "Evaluating on the test set...".to_string();
+            (network, lock.test_set.clone(), lock.config.clone(), lock.temperature)
+        };
+
         thread::spawn(move || {
-            let (config, train_set, test_set) = {
+            // The penalty of the evaluated weights, not the one of the last
+            // training epoch, which is stale once a model has been loaded.
+            let result = evaluate_with_loss(&network, &test_set, config.loss.build().as_ref()).and_then(|metrics| {
+                let penalty = Regularizer::new(&config, &network)?.penalty(&network);
+                Ok((metrics, penalty, Predictions::from_network(&network, &test_set)?))
+            });
+            let mut lock = state_clone.lock().unwrap();
+            match result {
+                Ok(((accuracy, test_loss), penalty, predictions)) => {
+                    lock.confusion_matrix = Some(ConfusionMatrix::from_predictions(&predictions));
+                    lock.selected_cell = None;
+                    let scaled = temperature
+                        .map(|temperature| CalibrationReport::new(&predictions.with_temperature(temperature)));
+                    lock.test_calibration = Some((CalibrationReport::new(&predictions), scaled));
+                    lock.test_curves = Some(OneVsRestCurves::new(&predictions));
+                    let test_loss = test_loss + penalty;
+                    let epoch = lock.train_accuracy_history.len().saturating_sub(1);
+                    lock.test_accuracy = accuracy;
+                    lock.test_loss = test_loss;
+                    lock.test_accuracy_history.push((epoch, accuracy));
+                    lock.test_loss_history.push((epoch, test_loss));
+                    lock.status = format!("Test set: accuracy {:.2}%, loss {:.4}", accuracy, test_loss);
+                }
+                Err(e) => lock.status = format!("Test evaluation failed: {}", e),
+            }
+            lock.needs_repaint = true;
+        });
+    }
+
+    /// Trains in the background, going on from `resume` when given.
+    fn spawn_training_thread(&self, state_clone: Arc<Mutex<AppState>>, resume: Option<Checkpoint>) {
+        thread::spawn(move || {
+            let (config, samples, continue_from) = {
                 let lock = state_clone.lock().unwrap();
+                let continue_from = if lock.continue_training {
+                    lock.network.clone().map(|network| (network, lock.optimizer.clone()))
+                } else {
+                    None
+                };
                 (
                     lock.config.clone(),
                     lock.train_set.clone(),
-                    lock.test_set.clone(),
+                    continue_from,
                 )
             };
 
-            let activations = config
-                .activations
-                .iter()
-                .map(|s| match s.as_str() {
-                    "sigmoid" => Activation::Sigmoid,
-                    "relu" => Activation::ReLU,
-                    "softmax" => Activation::Softmax,
-                    _ => Activation::Sigmoid,
-                })
-                .collect::<Vec<_>>();
+            let fail = |message: String| {
+                let mut lock = state_clone.lock().unwrap();
+                lock.status = message;
+                lock.training_state = TrainingState::Idle;
+                lock.needs_repaint = true;
+            };
 
-            let mut network = initialize_network(&config.layers, &activations);
+            let setup = match resume {
+                Some(checkpoint) => Ok(checkpoint),
+                None => Checkpoint::start(&config).map(|mut run| {
+                    if let Some((network, optimizer)) = continue_from {
+                        run.network = network;
+                        run.optimizer = optimizer.unwrap_or(run.optimizer);
+                    }
+                    run
+                }),
+            };
+            let setup = setup.and_then(|run| {
+                let (train_set, validation_set) =
+                    split_validation(samples, run.config.validation_split, run.config.stratify_validation)?;
+                Ok((Regularizer::new(&run.config, &run.network)?, run, train_set, validation_set))
+            });
+            let (regularizer, mut run, train_set, validation_set) = match setup {
+                Ok(setup) => setup,
+                Err(e) => return fail(format!("Invalid configuration: {}", e)),
+            };
+            let config = run.config.clone();
+            let mut stopped = None;
+            let loss = config.loss.build();
 
-            for epoch in 0..config.epochs {
+            for epoch in run.epoch..config.epochs {
                 {
                     let mut lock = state_clone.lock().unwrap();
                     match lock.training_state {
@@ -434,7 +1412,7 @@ impl GuiApp {
                         let mut lock = state_clone.lock().unwrap();
                         match lock.training_state {
                             TrainingState::Training => {
-                                lock.status = format!("Training... Epoch {}/{}", epoch + 1, config.epochs);
+                                lock.status = training_status(epoch, &config, &run.guard);
                                 break;
                             }
                             TrainingState::Complete => {
@@ -453,31 +1431,134 @@ impl GuiApp {
                     thread::sleep(Duration::from_millis(100));
                 }
 
-                train(&mut network, &train_set, 1, config.learning_rate);
+                let epoch_result = train_epoch(
+                    &mut run.network,
+                    &train_set,
+                    epoch,
+                    &config,
+                    &mut run.optimizer,
+                    &run.scheduler,
+                    &mut run.guard,
+                    &mut run.rng,
+                )
+                .and_then(|learning_rate| {
+                    let network = &run.network;
+                    let penalty = regularizer.penalty(network);
+                    let (train_accuracy, train_loss) = evaluate_with_loss(network, &train_set, loss.as_ref())?;
+                    let validation = if validation_set.is_empty() {
+                        None
+                    } else {
+                        let (accuracy, validation_loss) = evaluate_with_loss(network, &validation_set, loss.as_ref())?;
+                        Some((accuracy, validation_loss + penalty))
+                    };
+                    Ok((learning_rate, train_accuracy, train_loss + penalty, validation, penalty))
+                });
+                let (learning_rate, train_accuracy, train_loss, validation, penalty) = match epoch_result {
+                    Ok(result) => result,
+                    Err(e) => return fail(format!("Training failed: {}", e)),
+                };
+                let (monitored_accuracy, monitored_loss) = validation.unwrap_or((train_accuracy, train_loss));
+                run.scheduler.end_epoch(monitored_accuracy);
+                run.history.push(EpochMetrics {
+                    epoch: epoch + 1,
+                    train_accuracy,
+                    validation_accuracy: validation.map(|(accuracy, _)| accuracy),
+                    train_loss,
+                    validation_loss: validation.map(|(_, validation_loss)| validation_loss),
+                    penalty,
+                    learning_rate,
+                });
+                if let Some(early_stopping) = run.early_stopping.as_mut() {
+                    stopped = early_stopping.end_epoch(
+                        epoch,
+                        monitored_loss,
+                        monitored_accuracy,
+                        &run.network,
+                        &run.optimizer,
+                    );
+                }
+                run.epoch = epoch + 1;
+                if let Some(path) = scheduled_checkpoint(&config, run.epoch) {
+                    if let Err(e) = save_checkpoint(&path, &run) {
+                        return fail(format!("Failed to save checkpoint {}: {}", path.display(), e));
+                    }
+                }
 
                 {
                     let mut lock = state_clone.lock().unwrap();
-                    lock.train_accuracy = evaluate(&mut network, &train_set);
-                    lock.test_accuracy = evaluate(&mut network, &test_set);
-                    lock.network = Some(network.clone());
+                    lock.train_accuracy = train_accuracy;
+                    lock.train_accuracy_history.push(train_accuracy);
+                    lock.train_loss = train_loss;
+                    lock.train_loss_history.push(train_loss);
+                    if let Some((validation_accuracy, validation_loss)) = validation {
+                        lock.validation_accuracy = validation_accuracy;
+                        lock.validation_accuracy_history.push(validation_accuracy);
+                        lock.validation_loss = validation_loss;
+                        lock.validation_loss_history.push(validation_loss);
+                    }
+                    lock.penalty = penalty;
+                    lock.learning_rate = learning_rate;
+                    lock.learning_rate_history.push(learning_rate);
+                    lock.network = Some(run.network.clone());
+                    lock.optimizer = Some(run.optimizer.clone());
+                    lock.checkpoint = Some(run.clone());
                     lock.needs_repaint = true;
                 }
 
                 {
                     let mut lock = state_clone.lock().unwrap();
-                    lock.status = format!("Training... Epoch {}/{}", epoch + 1, config.epochs);
+                    lock.status = training_status(epoch, &config, &run.guard);
                     lock.progress = ((epoch + 1) as f32 / config.epochs as f32) * 100.0;
                 }
 
+                if stopped.is_some() {
+                    break;
+                }
+
                 thread::sleep(Duration::from_millis(10));
             }
 
+            let mut network = run.network;
+            let mut optimizer = run.optimizer;
+            let restored =
+                run.early_stopping.as_mut().and_then(EarlyStopping::take_best).map(|(best_epoch, best, best_optimizer)| {
+                    network = best;
+                    optimizer = best_optimizer;
+                    best_epoch
+                });
+            let temperature = if config.temperature_scaling {
+                match Predictions::from_network(&network, &validation_set) {
+                    Ok(predictions) => Some(fit_temperature(&predictions)),
+                    Err(e) => return fail(format!("Temperature scaling failed: {}", e)),
+                }
+            } else {
+                None
+            };
+
             {
                 let mut lock = state_clone.lock().unwrap();
+                let mut status = match &stopped {
+                    Some(stop) => format!("Stopped early at epoch {}: {}", stop.epoch + 1, stop),
+                    None => "Training complete".to_string(),
+                };
+                if let Some(best_epoch) = restored {
+                    status = format!("{}. Restored the weights from epoch {}", status, best_epoch + 1);
+                    lock.train_accuracy = lock.train_accuracy_history[best_epoch];
+                    lock.train_loss = lock.train_loss_history[best_epoch];
+                    if let Some(&accuracy) = lock.validation_accuracy_history.get(best_epoch) {
+                        lock.validation_accuracy = accuracy;
+                        lock.validation_loss = lock.validation_loss_history[best_epoch];
+                    }
+                }
+                if let Some(temperature) = temperature {
+                    status = format!("{}. Fitted temperature {:.4}", status, temperature);
+                }
+                lock.temperature = temperature;
                 lock.progress = 100.0;
-                lock.status = "Training complete".to_string();
+                lock.status = with_divergence_note(status, &run.guard);
                 lock.training_state = TrainingState::Complete;
                 lock.network = Some(network);
+                lock.optimizer = Some(optimizer);
                 lock.needs_repaint = true;
             }
         });
@@ -523,29 +1604,51 @@ impl eframe::App for GuiApp {
 
             self.ui_training_metrics(ui);
 
+            self.ui_confusion_matrix(ui);
+
+            self.ui_calibration(ui);
+
+            self.ui_curves(ui);
+
             ui.separator();
 
             self.ui_prediction(ui);
 
+            self.ui_network_summary(ui);
+
             self.ui_logs(ui);
         });
     }
 }
 
-fn predict(layers: &[Layer], sample: &Sample) -> usize {
-    let mut layers = layers.to_vec(); 
-    forward_pass(&mut layers, &sample.inputs);
-    let output_index = layers.len() - 1;
-    layers[output_index]
-        .neurons
-        .iter()
-        .enumerate()
-        .max_by(|a, b| a.1.activated_value.partial_cmp(&b.1.activated_value).unwrap())
-        .map(|(idx, _)| idx)
-        .unwrap_or(0)
+
+/// Status line while training, noting any batches dropped for non-finite values.
+fn training_status(epoch: usize, config: &Config, guard: &DivergenceGuard) -> String {
+    let mut status = format!("Training... Epoch {}/{} (batch size {}", epoch + 1, config.epochs, config.batch_size);
+    if config.threads > 1 {
+        status += &format!(", {} threads, {}", config.threads, config.parallel_mode.name());
+    }
+    status.push(')');
+    with_divergence_note(status, guard)
+}
+
+fn with_divergence_note(status: String, guard: &DivergenceGuard) -> String {
+    match &guard.first {
+        Some(first) => format!("{} | skipped {} batches, first: {}", status, guard.skipped_batches, first),
+        None => status,
+    }
+}
+
+/// Splits a comma-separated text field, treating an empty field as an empty list.
+fn parse_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
 }
 
-fn convert_to_image(inputs: &ndarray::Array1<f32>) -> Vec<u8> {
+fn convert_to_image(inputs: &ndarray::Array3<f32>) -> Vec<u8> {
     // was doing [pixel, pixel] instead of [pixel, pixel, pixel]
     // hours wasted: 4
        inputs.iter().flat_map(|&v| {
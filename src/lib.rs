@@ -3,5 +3,7 @@ pub mod network;
 pub mod training;
 pub mod utils;
 pub mod metrics;
+#[cfg(feature = "gui")]
 pub mod gui;
 pub mod config;
+pub mod error;
@@ -1,30 +1,56 @@
+use crate::data::dataset::{to_batch, Sample};
+use crate::error::Result;
+use crate::metrics::predictions::Predictions;
+use crate::network::Network;
+use ndarray::{Array1, ArrayView1};
+use crate::training::loss::Loss;
+use rayon::prelude::*;
 
-use crate::network::layer::Layer;
-use crate::data::dataset::Sample;
-use ndarray::Array1;
-use crate::training::trainer::forward_pass;
+/// Samples per chunk when evaluating a dataset. Chunks are evaluated in
+/// parallel but always split the same way, so the results do not depend on
+/// the number of threads.
+pub const EVALUATION_CHUNK_SIZE: usize = 256;
 
-fn argmax(vals: &Array1<f32>) -> usize {
+pub(crate) fn argmax(vals: ArrayView1<f32>) -> usize {
     vals.iter()
         .enumerate()
-        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+        .max_by(|a, b| a.1.total_cmp(b.1))
         .map(|(idx, _)| idx)
         .unwrap_or(0)
 }
 
 /// Evaluates the accuracy of the network on a given dataset.
-pub fn evaluate(layers: &mut [Layer], dataset: &[Sample]) -> f32 {
-    let out_idx = layers.len() - 1;
-    let mut correct = 0;
-    for sample in dataset {
-        forward_pass(layers, &sample.inputs);
-        let prediction = argmax(&layers[out_idx].activated_values());
-        let actual = argmax(&sample.target);
-        if prediction == actual {
-            correct += 1;
-        }
-    }
-    (correct as f32 / dataset.len() as f32) * 100.0
+pub fn evaluate(network: &Network, dataset: &[Sample]) -> Result<f32> {
+    let predictions = Predictions::from_network(network, dataset)?;
+    let correct = predictions
+        .predicted_labels()
+        .iter()
+        .zip(&predictions.labels)
+        .filter(|(predicted, actual)| predicted == actual)
+        .count();
+    Ok((correct as f32 / dataset.len().max(1) as f32) * 100.0)
 }
 
-
+/// Evaluates accuracy (as a percentage) and mean loss in a single pass. The
+/// per-sample losses are added up in dataset order, as a serial loop would.
+pub fn evaluate_with_loss(network: &Network, dataset: &[Sample], loss: &(dyn Loss + Sync)) -> Result<(f32, f32)> {
+    network.check_dataset(dataset)?;
+    let chunks = dataset
+        .par_chunks(EVALUATION_CHUNK_SIZE)
+        .map(|chunk| -> Result<(usize, Array1<f32>)> {
+            let (inputs, targets) = to_batch(&chunk.iter().collect::<Vec<_>>());
+            let outputs = network.predict_proba_batch(&inputs)?;
+            let correct = outputs
+                .rows()
+                .into_iter()
+                .zip(targets.rows())
+                .filter(|(output, target)| argmax(*output) == argmax(*target))
+                .count();
+            Ok((correct, loss.loss(&outputs, &targets)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let correct: usize = chunks.iter().map(|(correct, _)| correct).sum();
+    let total_loss = chunks.iter().flat_map(|(_, losses)| losses).fold(0.0, |total, &loss| total + loss);
+    let count = dataset.len().max(1) as f32;
+    Ok(((correct as f32 / count) * 100.0, total_loss / count))
+}
@@ -0,0 +1,147 @@
+use crate::metrics::predictions::Predictions;
+
+/// Bins of the reliability diagrams shown in the GUI and printed by the CLI.
+pub const RELIABILITY_BINS: usize = 15;
+
+/// The `k` reported for top-k accuracy.
+pub const REPORTED_TOP_K: [usize; 3] = [1, 3, 5];
+
+/// Range of temperatures `fit_temperature` searches.
+const TEMPERATURE_RANGE: (f32, f32) = (0.05, 20.0);
+
+/// Samples whose confidence, the probability of their predicted class, fell
+/// in `[lower, upper)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReliabilityBin {
+    pub lower: f32,
+    pub upper: f32,
+    pub count: usize,
+    /// Mean confidence of the samples, or `0` for an empty bin.
+    pub confidence: f32,
+    /// Share of the samples predicted correctly, or `0` for an empty bin.
+    pub accuracy: f32,
+}
+
+/// Accuracy against confidence over equal-width confidence bins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReliabilityDiagram {
+    pub bins: Vec<ReliabilityBin>,
+}
+
+impl ReliabilityDiagram {
+    pub fn new(predictions: &Predictions, bins: usize) -> Self {
+        let bins = bins.max(1);
+        let mut counts = vec![0usize; bins];
+        let mut confidence = vec![0.0f64; bins];
+        let mut correct = vec![0usize; bins];
+        for (row, &label) in predictions.probabilities.rows().into_iter().zip(&predictions.labels) {
+            let (predicted, &p) = row
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .expect("predictions have at least one class");
+            let bin = ((p * bins as f32) as usize).min(bins - 1);
+            counts[bin] += 1;
+            confidence[bin] += p as f64;
+            if predicted == label {
+                correct[bin] += 1;
+            }
+        }
+        let bins = (0..bins)
+            .map(|bin| {
+                let count = counts[bin];
+                let mean = |sum: f64| if count == 0 { 0.0 } else { (sum / count as f64) as f32 };
+                ReliabilityBin {
+                    lower: bin as f32 / bins as f32,
+                    upper: (bin + 1) as f32 / bins as f32,
+                    count,
+                    confidence: mean(confidence[bin]),
+                    accuracy: mean(correct[bin] as f64),
+                }
+            })
+            .collect();
+        ReliabilityDiagram { bins }
+    }
+
+    fn total(&self) -> usize {
+        self.bins.iter().map(|bin| bin.count).sum()
+    }
+
+    /// Mean gap between accuracy and confidence, weighted by the samples per bin.
+    pub fn expected_calibration_error(&self) -> f32 {
+        let total = self.total().max(1) as f32;
+        self.bins
+            .iter()
+            .map(|bin| bin.count as f32 / total * (bin.accuracy - bin.confidence).abs())
+            .sum()
+    }
+
+    /// Largest gap between accuracy and confidence over the non-empty bins.
+    pub fn maximum_calibration_error(&self) -> f32 {
+        self.bins
+            .iter()
+            .filter(|bin| bin.count > 0)
+            .map(|bin| (bin.accuracy - bin.confidence).abs())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Probabilistic quality of a set of predictions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationReport {
+    /// `(k, accuracy)` for every `REPORTED_TOP_K` up to the number of
+    /// classes, as percentages.
+    pub top_k_accuracy: Vec<(usize, f32)>,
+    pub negative_log_likelihood: f32,
+    pub brier_score: f32,
+    pub expected_calibration_error: f32,
+    pub maximum_calibration_error: f32,
+    pub reliability: ReliabilityDiagram,
+}
+
+impl CalibrationReport {
+    pub fn new(predictions: &Predictions) -> Self {
+        let reliability = ReliabilityDiagram::new(predictions, RELIABILITY_BINS);
+        CalibrationReport {
+            top_k_accuracy: REPORTED_TOP_K
+                .iter()
+                .filter(|&&k| k <= predictions.num_classes())
+                .map(|&k| (k, predictions.top_k_accuracy(k)))
+                .collect(),
+            negative_log_likelihood: predictions.negative_log_likelihood(),
+            brier_score: predictions.brier_score(),
+            expected_calibration_error: reliability.expected_calibration_error(),
+            maximum_calibration_error: reliability.maximum_calibration_error(),
+            reliability,
+        }
+    }
+}
+
+/// The softmax temperature minimizing the negative log-likelihood of
+/// `predictions`, found by golden-section search over its log. Fit it on the
+/// validation set; dividing the logits by it leaves the predicted classes
+/// unchanged.
+pub fn fit_temperature(predictions: &Predictions) -> f32 {
+    let nll = |log_temperature: f32| predictions.with_temperature(log_temperature.exp()).negative_log_likelihood();
+    let ratio = (5.0f32.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (TEMPERATURE_RANGE.0.ln(), TEMPERATURE_RANGE.1.ln());
+    let mut left = high - ratio * (high - low);
+    let mut right = low + ratio * (high - low);
+    let (mut left_nll, mut right_nll) = (nll(left), nll(right));
+    while high - low > 1e-4 {
+        if left_nll < right_nll {
+            high = right;
+            right = left;
+            right_nll = left_nll;
+            left = high - ratio * (high - low);
+            left_nll = nll(left);
+        } else {
+            low = left;
+            left = right;
+            left_nll = right_nll;
+            right = low + ratio * (high - low);
+            right_nll = nll(right);
+        }
+    }
+    ((low + high) / 2.0).exp()
+}
@@ -0,0 +1,208 @@
+use crate::data::dataset::Sample;
+use crate::error::Result;
+use crate::metrics::predictions::Predictions;
+use crate::metrics::ratio;
+use crate::network::Network;
+use ndarray::Array2;
+
+/// Counts of actual (rows) against predicted (columns) classes, remembering
+/// which samples landed in every cell.
+#[derive(Debug, Clone)]
+pub struct ConfusionMatrix {
+    counts: Array2<usize>,
+    /// Sample indices per cell, in row-major order.
+    samples: Vec<Vec<usize>>,
+}
+
+/// Precision, recall and F1 of one class, or an average of them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClassMetrics {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+    /// Number of samples of the class, or of all averaged classes.
+    pub support: usize,
+}
+
+/// Everything derived from a confusion matrix. Values are fractions in
+/// `[0, 1]`, except Cohen's kappa which can be negative.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassificationReport {
+    pub per_class: Vec<ClassMetrics>,
+    /// Unweighted mean over the classes that occur as actual or predicted.
+    pub macro_average: ClassMetrics,
+    /// Mean over the classes weighted by their support.
+    pub weighted_average: ClassMetrics,
+    pub accuracy: f32,
+    /// Mean recall over the classes that occur.
+    pub balanced_accuracy: f32,
+    pub cohens_kappa: f32,
+}
+
+impl ConfusionMatrix {
+    pub fn new(num_classes: usize) -> Self {
+        ConfusionMatrix {
+            counts: Array2::zeros((num_classes, num_classes)),
+            samples: vec![Vec::new(); num_classes * num_classes],
+        }
+    }
+
+    /// Predicts every sample of `dataset` with `network`.
+    pub fn from_network(network: &Network, dataset: &[Sample]) -> Result<Self> {
+        Ok(ConfusionMatrix::from_predictions(&Predictions::from_network(network, dataset)?))
+    }
+
+    /// Counts the most probable class of every prediction.
+    pub fn from_predictions(predictions: &Predictions) -> Self {
+        let mut matrix = ConfusionMatrix::new(predictions.num_classes());
+        for (index, (&actual, predicted)) in predictions.labels.iter().zip(predictions.predicted_labels()).enumerate() {
+            matrix.add(actual, predicted, index);
+        }
+        matrix
+    }
+
+    /// Records that sample `index` of class `actual` was predicted as `predicted`.
+    pub fn add(&mut self, actual: usize, predicted: usize, index: usize) {
+        let cell = actual * self.num_classes() + predicted;
+        self.counts[[actual, predicted]] += 1;
+        self.samples[cell].push(index);
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.counts.nrows()
+    }
+
+    pub fn count(&self, actual: usize, predicted: usize) -> usize {
+        self.counts[[actual, predicted]]
+    }
+
+    /// Indices of the samples of class `actual` predicted as `predicted`.
+    pub fn samples(&self, actual: usize, predicted: usize) -> &[usize] {
+        &self.samples[actual * self.num_classes() + predicted]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.sum()
+    }
+
+    /// Number of samples of class `class`.
+    pub fn support(&self, class: usize) -> usize {
+        self.counts.row(class).sum()
+    }
+
+    /// Number of samples predicted as `class`.
+    pub fn predicted(&self, class: usize) -> usize {
+        self.counts.column(class).sum()
+    }
+
+    pub fn correct(&self) -> usize {
+        self.counts.diag().sum()
+    }
+
+    /// Precision, recall and F1 of `class`; each is `0` when undefined.
+    pub fn class_metrics(&self, class: usize) -> ClassMetrics {
+        let correct = self.count(class, class) as f32;
+        let support = self.support(class);
+        let precision = ratio(correct, self.predicted(class) as f32);
+        let recall = ratio(correct, support as f32);
+        let f1 = ratio(2.0 * precision * recall, precision + recall);
+        ClassMetrics { precision, recall, f1, support }
+    }
+
+    pub fn report(&self) -> ClassificationReport {
+        let per_class: Vec<ClassMetrics> = (0..self.num_classes()).map(|class| self.class_metrics(class)).collect();
+        let occurring: Vec<usize> =
+            (0..self.num_classes()).filter(|&class| self.support(class) > 0 || self.predicted(class) > 0).collect();
+        let total = self.total();
+
+        let mut macro_average = ClassMetrics { support: total, ..ClassMetrics::default() };
+        let mut weighted_average = ClassMetrics { support: total, ..ClassMetrics::default() };
+        for &class in &occurring {
+            let metrics = per_class[class];
+            let weight = ratio(metrics.support as f32, total as f32);
+            macro_average.precision += metrics.precision / occurring.len() as f32;
+            macro_average.recall += metrics.recall / occurring.len() as f32;
+            macro_average.f1 += metrics.f1 / occurring.len() as f32;
+            weighted_average.precision += metrics.precision * weight;
+            weighted_average.recall += metrics.recall * weight;
+            weighted_average.f1 += metrics.f1 * weight;
+        }
+
+        let accuracy = ratio(self.correct() as f32, total as f32);
+        let supported: Vec<&ClassMetrics> = per_class.iter().filter(|metrics| metrics.support > 0).collect();
+        let balanced_accuracy =
+            ratio(supported.iter().map(|metrics| metrics.recall).sum::<f32>(), supported.len() as f32);
+        ClassificationReport {
+            per_class,
+            macro_average,
+            weighted_average,
+            accuracy,
+            balanced_accuracy,
+            cohens_kappa: self.cohens_kappa(),
+        }
+    }
+
+    /// Agreement between actual and predicted classes beyond what their
+    /// frequencies give by chance; `0` when chance agreement is already perfect.
+    pub fn cohens_kappa(&self) -> f32 {
+        let total = self.total() as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        let observed = self.correct() as f64 / total;
+        let expected = (0..self.num_classes())
+            .map(|class| self.support(class) as f64 * self.predicted(class) as f64)
+            .sum::<f64>()
+            / (total * total);
+        if expected >= 1.0 {
+            return 0.0;
+        }
+        ((observed - expected) / (1.0 - expected)) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn report_matches_hand_computed_values() {
+        let mut matrix = ConfusionMatrix::new(3);
+        for (index, (actual, predicted)) in [(0, 0), (0, 1), (1, 1), (1, 1), (2, 2), (2, 0)].into_iter().enumerate() {
+            matrix.add(actual, predicted, index);
+        }
+        assert_eq!(matrix.count(1, 1), 2);
+        assert_eq!(matrix.samples(2, 0), &[5]);
+        assert_eq!((matrix.support(1), matrix.predicted(1), matrix.correct()), (2, 3, 4));
+
+        let report = matrix.report();
+        let expected = [(0.5, 0.5, 0.5), (2.0 / 3.0, 1.0, 0.8), (1.0, 0.5, 2.0 / 3.0)];
+        for (metrics, (precision, recall, f1)) in report.per_class.iter().zip(expected) {
+            assert_close(metrics.precision, precision);
+            assert_close(metrics.recall, recall);
+            assert_close(metrics.f1, f1);
+            assert_eq!(metrics.support, 2);
+        }
+        assert_close(report.macro_average.precision, (0.5 + 2.0 / 3.0 + 1.0) / 3.0);
+        assert_close(report.weighted_average.f1, (0.5 + 0.8 + 2.0 / 3.0) / 3.0);
+        assert_close(report.accuracy, 4.0 / 6.0);
+        assert_close(report.balanced_accuracy, 2.0 / 3.0);
+        assert_close(report.cohens_kappa, 0.5);
+    }
+
+    #[test]
+    fn undefined_metrics_are_zero() {
+        let mut matrix = ConfusionMatrix::new(3);
+        matrix.add(0, 0, 0);
+        matrix.add(1, 0, 1);
+        let report = matrix.report();
+        assert_eq!(report.per_class[1], ClassMetrics { precision: 0.0, recall: 0.0, f1: 0.0, support: 1 });
+        // class 2 never occurs and is left out of the macro average
+        assert_close(report.macro_average.recall, 0.5);
+        assert_close(ConfusionMatrix::new(2).cohens_kappa(), 0.0);
+    }
+}
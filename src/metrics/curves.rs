@@ -0,0 +1,194 @@
+use crate::metrics::predictions::Predictions;
+use crate::metrics::ratio;
+
+/// Receiver operating characteristic: true positive rate against false
+/// positive rate as the decision threshold falls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RocCurve {
+    /// `(false positive rate, true positive rate)` from `(0, 0)` to `(1, 1)`.
+    pub points: Vec<(f32, f32)>,
+    /// Area under the curve by the trapezoidal rule.
+    pub auc: f32,
+}
+
+impl RocCurve {
+    fn new(points: Vec<(f32, f32)>) -> Self {
+        let auc = points.windows(2).map(|w| (w[1].0 - w[0].0) * (w[0].1 + w[1].1) / 2.0).sum();
+        RocCurve { points, auc }
+    }
+
+    /// The mean of `curves`, with every curve interpolated linearly over the
+    /// false positive rates at which any of them has a point.
+    pub fn average(curves: &[&RocCurve]) -> RocCurve {
+        let mut grid: Vec<f32> = curves.iter().flat_map(|curve| curve.points.iter().map(|&(fpr, _)| fpr)).collect();
+        grid.sort_by(f32::total_cmp);
+        grid.dedup();
+        let mut points = vec![(0.0, 0.0)];
+        points.extend(grid.into_iter().map(|fpr| {
+            let tpr = curves.iter().map(|curve| curve.true_positive_rate(fpr)).sum::<f32>();
+            (fpr, ratio(tpr, curves.len() as f32))
+        }));
+        RocCurve::new(points)
+    }
+
+    /// The true positive rate at `fpr`: the highest one when the curve rises
+    /// vertically there, else linearly interpolated between its neighbours.
+    fn true_positive_rate(&self, fpr: f32) -> f32 {
+        let after = self.points.partition_point(|&(x, _)| x <= fpr);
+        let Some(&(x0, y0)) = after.checked_sub(1).and_then(|i| self.points.get(i)) else {
+            return 0.0;
+        };
+        match self.points.get(after) {
+            Some(&(x1, y1)) if x0 < fpr => y0 + (y1 - y0) * (fpr - x0) / (x1 - x0),
+            _ => y0,
+        }
+    }
+}
+
+/// Precision against recall as the decision threshold falls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrCurve {
+    /// `(recall, precision)`, starting at `(0, 1)`.
+    pub points: Vec<(f32, f32)>,
+    /// Area under the curve as the average precision: the precision at every
+    /// threshold weighted by the recall it adds, which does not interpolate
+    /// between points and so does not overestimate the area.
+    pub average_precision: f32,
+}
+
+/// ROC and precision-recall curves of one binary problem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryCurves {
+    pub roc: RocCurve,
+    pub pr: PrCurve,
+    pub positives: usize,
+    pub negatives: usize,
+}
+
+impl BinaryCurves {
+    /// Curves for `scores`, where higher means more likely positive. Samples
+    /// with equal scores are taken as one threshold.
+    pub fn new(scores: &[(f32, bool)]) -> Self {
+        let mut sorted = scores.to_vec();
+        sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let positives = sorted.iter().filter(|(_, positive)| *positive).count();
+        let negatives = sorted.len() - positives;
+
+        let mut roc = vec![(0.0, 0.0)];
+        let mut pr = vec![(0.0, 1.0)];
+        let (mut true_positives, mut false_positives) = (0, 0);
+        for (i, &(score, positive)) in sorted.iter().enumerate() {
+            if positive {
+                true_positives += 1;
+            } else {
+                false_positives += 1;
+            }
+            if sorted.get(i + 1).is_some_and(|next| next.0 == score) {
+                continue;
+            }
+            let recall = ratio(true_positives as f32, positives as f32);
+            roc.push((ratio(false_positives as f32, negatives as f32), recall));
+            pr.push((recall, ratio(true_positives as f32, (true_positives + false_positives) as f32)));
+        }
+
+        let average_precision = pr.windows(2).map(|w| (w[1].0 - w[0].0) * w[1].1).sum();
+        BinaryCurves {
+            roc: RocCurve::new(roc),
+            pr: PrCurve { points: pr, average_precision },
+            positives,
+            negatives,
+        }
+    }
+
+    /// Whether both classes occur, without which the areas mean nothing.
+    pub fn is_defined(&self) -> bool {
+        self.positives > 0 && self.negatives > 0
+    }
+}
+
+/// One-vs-rest curves of every class, with their micro and macro averages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneVsRestCurves {
+    pub per_class: Vec<BinaryCurves>,
+    /// Curves of every `(sample, class)` pair pooled into one binary problem.
+    pub micro: BinaryCurves,
+    /// `RocCurve::average` of the classes that have positives and negatives.
+    pub macro_roc: RocCurve,
+    /// Mean ROC AUC over the same classes. Unlike the area under `macro_roc`,
+    /// this is what macro-averaged ROC AUC usually means.
+    pub macro_roc_auc: f32,
+    /// Mean average precision over those classes.
+    pub macro_average_precision: f32,
+}
+
+impl OneVsRestCurves {
+    /// Scores every class by its predicted probability.
+    pub fn new(predictions: &Predictions) -> Self {
+        let class_scores = |class: usize| -> Vec<(f32, bool)> {
+            predictions
+                .probabilities
+                .column(class)
+                .iter()
+                .zip(&predictions.labels)
+                .map(|(&p, &label)| (p, label == class))
+                .collect()
+        };
+        let per_class: Vec<BinaryCurves> =
+            (0..predictions.num_classes()).map(|class| BinaryCurves::new(&class_scores(class))).collect();
+        let pooled: Vec<(f32, bool)> = (0..predictions.num_classes()).flat_map(class_scores).collect();
+
+        let defined: Vec<&BinaryCurves> = per_class.iter().filter(|curves| curves.is_defined()).collect();
+        let mean = |area: fn(&BinaryCurves) -> f32| {
+            if defined.is_empty() {
+                0.0
+            } else {
+                defined.iter().map(|curves| area(curves)).sum::<f32>() / defined.len() as f32
+            }
+        };
+        OneVsRestCurves {
+            macro_roc: RocCurve::average(&defined.iter().map(|curves| &curves.roc).collect::<Vec<_>>()),
+            macro_roc_auc: mean(|curves| curves.roc.auc),
+            macro_average_precision: mean(|curves| curves.pr.average_precision),
+            micro: BinaryCurves::new(&pooled),
+            per_class,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn binary_curves_match_hand_computed_areas() {
+        let curves = BinaryCurves::new(&[(0.1, false), (0.4, false), (0.35, true), (0.8, true)]);
+        assert_eq!(curves.roc.points, vec![(0.0, 0.0), (0.0, 0.5), (0.5, 0.5), (0.5, 1.0), (1.0, 1.0)]);
+        assert_close(curves.roc.auc, 0.75);
+        assert_close(curves.pr.average_precision, 0.5 + 0.5 * 2.0 / 3.0);
+        assert!(curves.is_defined());
+    }
+
+    #[test]
+    fn tied_scores_are_one_threshold() {
+        let curves = BinaryCurves::new(&[(0.5, true), (0.5, false), (0.2, false)]);
+        assert_eq!(curves.roc.points, vec![(0.0, 0.0), (0.5, 1.0), (1.0, 1.0)]);
+        assert_close(curves.roc.auc, 0.75);
+        assert_close(curves.pr.average_precision, 0.5);
+        assert!(!BinaryCurves::new(&[(0.3, true)]).is_defined());
+    }
+
+    #[test]
+    fn macro_roc_averages_interpolated_curves() {
+        let perfect = BinaryCurves::new(&[(0.9, true), (0.1, false)]);
+        let mixed = BinaryCurves::new(&[(0.1, false), (0.4, false), (0.35, true), (0.8, true)]);
+        let average = RocCurve::average(&[&perfect.roc, &mixed.roc]);
+        assert_eq!(average.points, vec![(0.0, 0.0), (0.0, 0.75), (0.5, 1.0), (1.0, 1.0)]);
+        assert_close(average.auc, 0.9375);
+        assert_close(mixed.roc.true_positive_rate(0.25), 0.5);
+        assert_close(perfect.roc.true_positive_rate(0.25), 1.0);
+    }
+}
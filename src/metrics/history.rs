@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// Metrics recorded at the end of one training epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochMetrics {
+    pub epoch: usize,
+    pub train_accuracy: f32,
+    /// `None` when no validation set was held out.
+    pub validation_accuracy: Option<f32>,
+    /// Losses include `penalty`.
+    pub train_loss: f32,
+    pub validation_loss: Option<f32>,
+    /// Regularization term of the loss at the end of the epoch.
+    #[serde(default)]
+    pub penalty: f32,
+    pub learning_rate: f32,
+}
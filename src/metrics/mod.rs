@@ -1 +1,15 @@
 pub mod accuracy;
+pub mod calibration;
+pub mod confusion;
+pub mod curves;
+pub mod history;
+pub mod predictions;
+
+/// `numerator / denominator`, or `0` for a zero denominator.
+pub(crate) fn ratio(numerator: f32, denominator: f32) -> f32 {
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
@@ -0,0 +1,115 @@
+use crate::data::dataset::{to_batch, Sample};
+use crate::error::Result;
+use crate::metrics::accuracy::{argmax, EVALUATION_CHUNK_SIZE};
+use crate::network::Network;
+use ndarray::{concatenate, Array2, Axis};
+use rayon::prelude::*;
+
+/// Probabilities below this count as this when taking their log.
+const MIN_PROBABILITY: f32 = 1e-12;
+
+/// Output-layer probabilities of a network for a dataset, one row per
+/// sample, together with the true labels.
+#[derive(Debug, Clone)]
+pub struct Predictions {
+    pub probabilities: Array2<f32>,
+    pub labels: Vec<usize>,
+}
+
+impl Predictions {
+    /// Predicts chunks of `dataset` in parallel.
+    pub fn from_network(network: &Network, dataset: &[Sample]) -> Result<Self> {
+        network.check_dataset(dataset)?;
+        let batches = dataset
+            .par_chunks(EVALUATION_CHUNK_SIZE)
+            .map(|chunk| {
+                let (inputs, _) = to_batch(&chunk.iter().collect::<Vec<_>>());
+                network.predict_proba_batch(&inputs)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let views: Vec<_> = batches.iter().map(|batch| batch.view()).collect();
+        let probabilities = if views.is_empty() {
+            Array2::zeros((0, network.output_size()))
+        } else {
+            concatenate(Axis(0), &views).expect("batches of one network have the same width")
+        };
+        Ok(Predictions { probabilities, labels: dataset.iter().map(Sample::label).collect() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.probabilities.ncols()
+    }
+
+    /// Index of the most probable class of every sample.
+    pub fn predicted_labels(&self) -> Vec<usize> {
+        self.probabilities.rows().into_iter().map(argmax).collect()
+    }
+
+    /// Percentage of samples whose true class is among their `k` most
+    /// probable ones. Ties go against the true class.
+    pub fn top_k_accuracy(&self, k: usize) -> f32 {
+        let hits = self
+            .probabilities
+            .rows()
+            .into_iter()
+            .zip(&self.labels)
+            .filter(|(row, &label)| row.iter().filter(|&&p| p >= row[label]).count() <= k)
+            .count();
+        hits as f32 / self.len().max(1) as f32 * 100.0
+    }
+
+    /// Mean negative log of the probability given to the true class.
+    pub fn negative_log_likelihood(&self) -> f32 {
+        let total: f64 = self
+            .probabilities
+            .rows()
+            .into_iter()
+            .zip(&self.labels)
+            .map(|(row, &label)| -(row[label].max(MIN_PROBABILITY) as f64).ln())
+            .sum();
+        (total / self.len().max(1) as f64) as f32
+    }
+
+    /// Mean over the samples of the squared distance between the
+    /// probabilities and the one-hot true class, in `[0, 2]`.
+    pub fn brier_score(&self) -> f32 {
+        let total: f64 = self
+            .probabilities
+            .rows()
+            .into_iter()
+            .zip(&self.labels)
+            .map(|(row, &label)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(class, &p)| {
+                        let error = p as f64 - if class == label { 1.0 } else { 0.0 };
+                        error * error
+                    })
+                    .sum::<f64>()
+            })
+            .sum();
+        (total / self.len().max(1) as f64) as f32
+    }
+
+    /// The probabilities a softmax output would give with its logits divided
+    /// by `temperature`. Softmax only shifts the logits by a constant, so they
+    /// are recovered as the log probabilities.
+    pub fn with_temperature(&self, temperature: f32) -> Predictions {
+        let mut probabilities = self.probabilities.mapv(|p| p.max(MIN_PROBABILITY).ln() / temperature);
+        for mut row in probabilities.rows_mut() {
+            let max = row.fold(f32::NEG_INFINITY, |max, &v| max.max(v));
+            row.mapv_inplace(|v| (v - max).exp());
+            let sum = row.sum();
+            row /= sum;
+        }
+        Predictions { probabilities, labels: self.labels.clone() }
+    }
+}
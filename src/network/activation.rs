@@ -1,32 +1,178 @@
 use ndarray::Array1;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Element-wise activation functions.
+///
+/// `derivate(raw, activated)` receives both the pre-activation `z` and the
+/// output `a = f(z)`; each variant documents which of the two its derivative
+/// is computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Activation {
+    /// `1 / (1 + e^-z)`. Derivative from the output: `a * (1 - a)`.
     Sigmoid,
+    /// `tanh(z)`. Derivative from the output: `1 - a^2`.
+    Tanh,
+    /// `max(0, z)`. Derivative from the pre-activation: `1` if `z > 0`, else `0`.
     ReLU,
-    Softmax
-    // more later
+    /// `z` if `z > 0`, else `alpha * z`. Derivative from the pre-activation.
+    LeakyReLU(f32),
+    /// Leaky ReLU whose negative slope is learned per neuron; the value is the
+    /// initial slope. The layer owns the learned slopes, so `activate` and
+    /// `derivate` here use the initial slope. Derivative from the pre-activation.
+    PReLU(f32),
+    /// `z` if `z > 0`, else `alpha * (e^z - 1)`. Derivative from the pre-activation.
+    ELU(f32),
+    /// Scaled ELU with the self-normalizing constants. Derivative from the pre-activation.
+    SELU,
+    /// Gaussian error linear unit, tanh approximation. Derivative from the pre-activation.
+    GELU,
+    /// `z * sigmoid(z)`, also known as SiLU. Derivative from the pre-activation.
+    Swish,
+    /// `z * tanh(softplus(z))`. Derivative from the pre-activation.
+    Mish,
+    /// `ln(1 + e^z)`. Derivative from the pre-activation: `sigmoid(z)`.
+    Softplus,
+    /// `clamp(z / 6 + 1/2, 0, 1)`. Derivative from the pre-activation.
+    HardSigmoid,
+    /// Identity. Derivative is `1`.
+    Linear,
+    /// Applied across the whole layer, not element-wise; see `softmax`. The
+    /// layer applies its Jacobian directly, so `derivate` is not used.
+    Softmax,
 }
 
+const SELU_ALPHA: f32 = 1.673_263_2;
+const SELU_SCALE: f32 = 1.050_701;
+const GELU_COEFF: f32 = 0.044_715;
+const SQRT_2_OVER_PI: f32 = 0.797_884_6;
+
 impl Activation {
-    
+
     pub fn activate(&self, input: f32) -> f32 {
-        match self {
+        match *self {
             Activation::Sigmoid => sigmoid(input),
+            Activation::Tanh => input.tanh(),
             Activation::ReLU => relu(input),
+            Activation::LeakyReLU(alpha) | Activation::PReLU(alpha) => leaky_relu(input, alpha),
+            Activation::ELU(alpha) => elu(input, alpha),
+            Activation::SELU => SELU_SCALE * elu(input, SELU_ALPHA),
+            Activation::GELU => gelu(input),
+            Activation::Swish => input * sigmoid(input),
+            Activation::Mish => input * softplus(input).tanh(),
+            Activation::Softplus => softplus(input),
+            Activation::HardSigmoid => (input / 6.0 + 0.5).clamp(0.0, 1.0),
+            Activation::Linear => input,
             Activation::Softmax => input // softmax is apply seperately
         }
     }
 
-    pub fn derivate(&self, input: f32) -> f32 {
-        match self {
-            Activation::Sigmoid => sigmoid_derivative(input),
-            Activation::ReLU => relu_derivative(input),
+    pub fn derivate(&self, raw: f32, activated: f32) -> f32 {
+        match *self {
+            Activation::Sigmoid => sigmoid_derivative(activated),
+            Activation::Tanh => 1.0 - activated * activated,
+            Activation::ReLU => relu_derivative(raw),
+            Activation::LeakyReLU(alpha) | Activation::PReLU(alpha) => {
+                if raw > 0.0 { 1.0 } else { alpha }
+            }
+            Activation::ELU(alpha) => {
+                if raw > 0.0 { 1.0 } else { alpha * raw.exp() }
+            }
+            Activation::SELU => {
+                if raw > 0.0 { SELU_SCALE } else { SELU_SCALE * SELU_ALPHA * raw.exp() }
+            }
+            Activation::GELU => gelu_derivative(raw),
+            Activation::Swish => {
+                let s = sigmoid(raw);
+                s + raw * s * (1.0 - s)
+            }
+            Activation::Mish => {
+                let t = softplus(raw).tanh();
+                t + raw * (1.0 - t * t) * sigmoid(raw)
+            }
+            Activation::Softplus => sigmoid(raw),
+            Activation::HardSigmoid => {
+                if raw > -3.0 && raw < 3.0 { 1.0 / 6.0 } else { 0.0 }
+            }
+            Activation::Linear => 1.0,
             Activation::Softmax => 1.0 // this is not used
         }
     }
+
+    /// Activations that behave like ReLU for positive inputs, which want the
+    /// larger He initialization scale.
+    pub fn is_rectifier(&self) -> bool {
+        matches!(
+            self,
+            Activation::ReLU
+                | Activation::LeakyReLU(_)
+                | Activation::PReLU(_)
+                | Activation::ELU(_)
+                | Activation::GELU
+                | Activation::Swish
+                | Activation::Mish
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseActivationError(pub String);
+
+impl fmt::Display for ParseActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown activation '{}'", self.0)
+    }
 }
+
+impl std::error::Error for ParseActivationError {}
+
+/// Parses names like `relu`, `leaky_relu(0.1)` or `elu`. Matching ignores case,
+/// and parametrized activations fall back to their usual default when no
+/// parameter is given.
+impl FromStr for Activation {
+    type Err = ParseActivationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseActivationError(s.to_string());
+        let lower = s.trim().to_lowercase();
+        let (name, param) = match lower.split_once('(') {
+            Some((name, rest)) => {
+                let value = rest.strip_suffix(')').ok_or_else(err)?;
+                (name.trim().to_string(), Some(value.trim().parse::<f32>().map_err(|_| err())?))
+            }
+            None => (lower, None),
+        };
+
+        let activation = match name.replace(['_', '-'], "").as_str() {
+            "sigmoid" => Activation::Sigmoid,
+            "tanh" => Activation::Tanh,
+            "relu" => Activation::ReLU,
+            "leakyrelu" => Activation::LeakyReLU(param.unwrap_or(0.01)),
+            "prelu" => Activation::PReLU(param.unwrap_or(0.25)),
+            "elu" => Activation::ELU(param.unwrap_or(1.0)),
+            "selu" => Activation::SELU,
+            "gelu" => Activation::GELU,
+            "swish" | "silu" => Activation::Swish,
+            "mish" => Activation::Mish,
+            "softplus" => Activation::Softplus,
+            "hardsigmoid" => Activation::HardSigmoid,
+            "linear" | "identity" => Activation::Linear,
+            "softmax" => Activation::Softmax,
+            _ => return Err(err()),
+        };
+
+        let takes_param = matches!(
+            activation,
+            Activation::LeakyReLU(_) | Activation::PReLU(_) | Activation::ELU(_)
+        );
+        if param.is_some() && !takes_param {
+            return Err(err());
+        }
+        Ok(activation)
+    }
+}
+
 fn sigmoid(z: f32) -> f32 {
     1.0 / (1.0 + (-z).exp())
 }
@@ -39,13 +185,37 @@ fn relu(z: f32) -> f32 {
     z.max(0.0)
 }
 
-fn relu_derivative(a: f32) -> f32 {
-    if a > 0.0 { 1.0 } else { 0.0 }
+fn relu_derivative(z: f32) -> f32 {
+    if z > 0.0 { 1.0 } else { 0.0 }
+}
+
+fn leaky_relu(z: f32, alpha: f32) -> f32 {
+    if z > 0.0 { z } else { alpha * z }
+}
+
+fn elu(z: f32, alpha: f32) -> f32 {
+    if z > 0.0 { z } else { alpha * z.exp_m1() }
+}
+
+fn softplus(z: f32) -> f32 {
+    // stable for large |z|
+    z.max(0.0) + (-z.abs()).exp().ln_1p()
+}
+
+fn gelu(z: f32) -> f32 {
+    0.5 * z * (1.0 + (SQRT_2_OVER_PI * (z + GELU_COEFF * z * z * z)).tanh())
 }
+
+fn gelu_derivative(z: f32) -> f32 {
+    let inner = SQRT_2_OVER_PI * (z + GELU_COEFF * z * z * z);
+    let t = inner.tanh();
+    let inner_derivative = SQRT_2_OVER_PI * (1.0 + 3.0 * GELU_COEFF * z * z);
+    0.5 * (1.0 + t) + 0.5 * z * (1.0 - t * t) * inner_derivative
+}
+
 pub fn softmax(z: &Array1<f32>) -> Vec<f32> {
     let max_z = z.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
     let exps: Vec<f32> = z.iter().map(|&x| (x - max_z).exp()).collect();
     let sum_exps: f32 = exps.iter().sum();
     exps.iter().map(|&x| x / sum_exps).collect()
 }
-
@@ -0,0 +1,309 @@
+use crate::network::activation::Activation;
+use crate::network::initializer::Initializer;
+use ndarray::{Array1, Array2, ArrayView1, ArrayViewD, ArrayViewMutD, Axis, Zip};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// `(channels, height, width)` of one sample. Spatial layers read each row of
+/// a batch as values in this order, channel by channel and row by row.
+pub type ImageShape = (usize, usize, usize);
+
+pub(crate) fn shape_len((channels, height, width): ImageShape) -> usize {
+    channels * height * width
+}
+
+/// Geometry of a square sliding window, shared by convolution and pooling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    pub size: usize,
+    pub stride: usize,
+    /// Zeros added on every side of the input.
+    pub padding: usize,
+    /// Spacing between the window's taps; `1` means adjacent values.
+    pub dilation: usize,
+}
+
+impl Window {
+    /// A `size x size` window with stride 1, no padding and no dilation.
+    pub fn new(size: usize) -> Self {
+        Window { size, stride: 1, padding: 0, dilation: 1 }
+    }
+
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = stride;
+        self
+    }
+
+    pub fn padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn dilation(mut self, dilation: usize) -> Self {
+        self.dilation = dilation;
+        self
+    }
+
+    /// Height and width of the output for an input of the given size, or
+    /// `None` when the window does not fit. Padding has to be smaller than
+    /// the window, and every window position has to cover at least one
+    /// input value rather than only padding.
+    pub fn output_dims(&self, height: usize, width: usize) -> Option<(usize, usize)> {
+        if self.size == 0 || self.stride == 0 || self.dilation == 0 || self.padding >= self.size {
+            return None;
+        }
+        let span = self.dilation * (self.size - 1) + 1;
+        let side = |len: usize| {
+            let padded = len + 2 * self.padding;
+            if padded < span {
+                return None;
+            }
+            let out = (padded - span) / self.stride + 1;
+            let covers_input = |o: usize| {
+                (0..self.size).any(|k| (o * self.stride + k * self.dilation).checked_sub(self.padding).is_some_and(|i| i < len))
+            };
+            (0..out).all(covers_input).then_some(out)
+        };
+        Some((side(height)?, side(width)?))
+    }
+
+    /// Input row and column read by tap `(ki, kj)` of the window at output
+    /// position `(oy, ox)`, or `None` when it falls in the padding.
+    pub(crate) fn tap(&self, (height, width): (usize, usize), (oy, ox): (usize, usize), (ki, kj): (usize, usize)) -> Option<(usize, usize)> {
+        let y = (oy * self.stride + ki * self.dilation).checked_sub(self.padding)?;
+        let x = (ox * self.stride + kj * self.dilation).checked_sub(self.padding)?;
+        (y < height && x < width).then_some((y, x))
+    }
+}
+
+/// Unfolds one sample into a matrix with one column per output position and
+/// one row per `(channel, ki, kj)` tap, so the convolution becomes a product.
+/// Padding reads as zero.
+pub(crate) fn im2col(input: ArrayView1<f32>, (channels, height, width): ImageShape, window: &Window) -> Array2<f32> {
+    let (out_h, out_w) = window.output_dims(height, width).unwrap_or((0, 0));
+    let taps = window.size * window.size;
+    let mut columns = Array2::zeros((channels * taps, out_h * out_w));
+    for c in 0..channels {
+        for ki in 0..window.size {
+            for kj in 0..window.size {
+                let row = c * taps + ki * window.size + kj;
+                for oy in 0..out_h {
+                    for ox in 0..out_w {
+                        if let Some((y, x)) = window.tap((height, width), (oy, ox), (ki, kj)) {
+                            columns[[row, oy * out_w + ox]] = input[(c * height + y) * width + x];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    columns
+}
+
+/// Inverse of `im2col` for gradients: adds every entry of `columns` back onto
+/// the input value it was read from.
+pub(crate) fn col2im(columns: &Array2<f32>, (channels, height, width): ImageShape, window: &Window) -> Array1<f32> {
+    let (out_h, out_w) = window.output_dims(height, width).unwrap_or((0, 0));
+    let taps = window.size * window.size;
+    let mut input = Array1::zeros(channels * height * width);
+    for c in 0..channels {
+        for ki in 0..window.size {
+            for kj in 0..window.size {
+                let row = c * taps + ki * window.size + kj;
+                for oy in 0..out_h {
+                    for ox in 0..out_w {
+                        if let Some((y, x)) = window.tap((height, width), (oy, ox), (ki, kj)) {
+                            input[(c * height + y) * width + x] += columns[[row, oy * out_w + ox]];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    input
+}
+
+/// A 2D convolution followed by an element-wise activation. Row `o` of
+/// `weights` holds kernel `o` flattened over `(input channel, row, column)`,
+/// so `weights` has shape `(out_channels, in_channels * size * size)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Conv2dRepr")]
+pub struct Conv2d {
+    pub weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    pub activation: Activation,
+    pub window: Window,
+    input_shape: ImageShape,
+
+    /// `im2col` of every sample in the last batch.
+    #[serde(skip)]
+    pub columns: Vec<Array2<f32>>,
+    #[serde(skip)]
+    pub raw_values: Array2<f32>,
+    #[serde(skip)]
+    pub activated_values: Array2<f32>,
+    #[serde(skip)]
+    pub weight_gradients: Array2<f32>,
+    #[serde(skip)]
+    pub bias_gradients: Array1<f32>,
+}
+
+impl Conv2d {
+    /// A convolution over inputs of `input_shape` with `out_channels` kernels.
+    /// The window must fit the input, and the activation must be element-wise.
+    pub fn new<R: Rng + ?Sized>(
+        input_shape: ImageShape,
+        out_channels: usize,
+        window: Window,
+        activation: Activation,
+        weight_init: Initializer,
+        bias_init: Initializer,
+        rng: &mut R,
+    ) -> Self {
+        let fan_in = input_shape.0 * window.size * window.size;
+        let fan_out = out_channels * window.size * window.size;
+        let weights = weight_init.sample((out_channels, fan_in), fan_in, fan_out, activation, rng);
+        let biases = bias_init.sample((1, out_channels), fan_in, fan_out, activation, rng);
+        Self::from_parameters(weights, biases.remove_axis(Axis(0)), activation, window, input_shape)
+    }
+
+    pub fn from_parameters(
+        weights: Array2<f32>,
+        biases: Array1<f32>,
+        activation: Activation,
+        window: Window,
+        input_shape: ImageShape,
+    ) -> Self {
+        let dim = weights.dim();
+        let mut layer = Conv2d {
+            weights,
+            biases,
+            activation,
+            window,
+            input_shape,
+            columns: Vec::new(),
+            raw_values: Array2::zeros((0, 0)),
+            activated_values: Array2::zeros((0, 0)),
+            weight_gradients: Array2::zeros(dim),
+            bias_gradients: Array1::zeros(dim.0),
+        };
+        layer.raw_values = Array2::zeros((0, layer.size()));
+        layer.activated_values = Array2::zeros((0, layer.size()));
+        layer
+    }
+
+    pub fn input_shape(&self) -> ImageShape {
+        self.input_shape
+    }
+
+    pub fn output_shape(&self) -> ImageShape {
+        let (_, height, width) = self.input_shape;
+        let (out_h, out_w) = self.window.output_dims(height, width).unwrap_or((0, 0));
+        (self.weights.nrows(), out_h, out_w)
+    }
+
+    pub fn size(&self) -> usize {
+        shape_len(self.output_shape())
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        shape_len(self.input_shape)
+    }
+
+    pub fn forward(&mut self, inputs: &Array2<f32>) -> Array2<f32> {
+        let (columns, raw) = self.convolve(inputs);
+        self.columns = columns;
+        self.activated_values = raw.mapv(|z| self.activation.activate(z));
+        self.raw_values = raw;
+        self.activated_values.clone()
+    }
+
+    /// Like `forward`, but leaves the caches untouched.
+    pub fn infer(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        self.convolve(inputs).1.mapv(|z| self.activation.activate(z))
+    }
+
+    /// Backward pass for the batch cached by `forward`. Fills the parameter
+    /// gradients, averaged over the batch, and returns the gradient with
+    /// respect to this layer's inputs.
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let mut deltas = grad_output.clone();
+        let activation = self.activation;
+        Zip::from(&mut deltas)
+            .and(&self.raw_values)
+            .and(&self.activated_values)
+            .for_each(|g, &z, &a| *g *= activation.derivate(z, a));
+
+        let (out_channels, out_h, out_w) = self.output_shape();
+        let positions = out_h * out_w;
+        let mut weight_gradients = Array2::zeros(self.weights.dim());
+        let mut bias_gradients = Array1::zeros(out_channels);
+        let mut grad_input = Array2::zeros((deltas.nrows(), self.num_inputs()));
+        for ((delta, columns), mut grad) in deltas.rows().into_iter().zip(&self.columns).zip(grad_input.rows_mut()) {
+            let delta = Array2::from_shape_fn((out_channels, positions), |(o, p)| delta[o * positions + p]);
+            weight_gradients += &delta.dot(&columns.t());
+            bias_gradients += &delta.sum_axis(Axis(1));
+            grad.assign(&col2im(&self.weights.t().dot(&delta), self.input_shape, &self.window));
+        }
+
+        let batch_size = deltas.nrows().max(1) as f32;
+        self.weight_gradients = weight_gradients / batch_size;
+        self.bias_gradients = bias_gradients / batch_size;
+        grad_input
+    }
+
+    /// Trainable parameters paired with their gradients from the last backward
+    /// pass, weights first.
+    pub fn parameters_mut(&mut self) -> Vec<(ArrayViewMutD<'_, f32>, ArrayViewD<'_, f32>)> {
+        vec![
+            (self.weights.view_mut().into_dyn(), self.weight_gradients.view().into_dyn()),
+            (self.biases.view_mut().into_dyn(), self.bias_gradients.view().into_dyn()),
+        ]
+    }
+
+    /// The unfolded inputs of every sample and the raw outputs, one sample per row.
+    fn convolve(&self, inputs: &Array2<f32>) -> (Vec<Array2<f32>>, Array2<f32>) {
+        let biases = self.biases.view().insert_axis(Axis(1));
+        let mut raw = Array2::zeros((inputs.nrows(), self.size()));
+        let columns = inputs
+            .rows()
+            .into_iter()
+            .zip(raw.rows_mut())
+            .map(|(sample, mut out)| {
+                let columns = im2col(sample, self.input_shape, &self.window);
+                let z = self.weights.dot(&columns) + biases;
+                out.iter_mut().zip(z.iter()).for_each(|(out, &z)| *out = z);
+                columns
+            })
+            .collect();
+        (columns, raw)
+    }
+}
+
+#[derive(Deserialize)]
+struct Conv2dRepr {
+    weights: Array2<f32>,
+    biases: Array1<f32>,
+    activation: Activation,
+    window: Window,
+    input_shape: ImageShape,
+}
+
+impl TryFrom<Conv2dRepr> for Conv2d {
+    type Error = String;
+
+    fn try_from(repr: Conv2dRepr) -> Result<Self, Self::Error> {
+        let (channels, height, width) = repr.input_shape;
+        if repr.window.output_dims(height, width).is_none() {
+            return Err(format!("conv2d window {:?} does not fit a {}x{} input", repr.window, height, width));
+        }
+        let kernel_len = channels * repr.window.size * repr.window.size;
+        if repr.weights.ncols() != kernel_len {
+            return Err(format!("conv2d kernels have {} weights but expected {}", repr.weights.ncols(), kernel_len));
+        }
+        if repr.weights.nrows() != repr.biases.len() {
+            return Err(format!("conv2d has {} kernels but {} biases", repr.weights.nrows(), repr.biases.len()));
+        }
+        Ok(Conv2d::from_parameters(repr.weights, repr.biases, repr.activation, repr.window, repr.input_shape))
+    }
+}
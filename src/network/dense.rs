@@ -0,0 +1,282 @@
+use crate::network::activation::{softmax, Activation};
+use crate::network::initializer::Initializer;
+use crate::network::neuron::Neuron;
+use ndarray::{Array1, Array2, ArrayViewD, ArrayViewMutD, Axis, Zip};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// A fully connected layer. Row `i` of `weights` holds the incoming weights of
+/// neuron `i`, so `weights` has shape `(num_neurons, num_inputs)`.
+///
+/// The input layer is represented as a layer with zero inputs; it passes its
+/// inputs through unchanged.
+///
+/// The caches below are filled by the forward and backward passes, one row per
+/// sample in the batch, and are not serialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "DenseRepr")]
+pub struct Dense {
+    pub weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    pub activation: Option<Activation>,
+    /// Learned negative slopes, one per neuron. Only used by `PReLU` layers
+    /// and empty otherwise.
+    #[serde(skip_serializing_if = "Array1::is_empty")]
+    pub slopes: Array1<f32>,
+
+    #[serde(skip)]
+    pub inputs: Array2<f32>,
+    #[serde(skip)]
+    pub raw_values: Array2<f32>,
+    #[serde(skip)]
+    pub activated_values: Array2<f32>,
+    #[serde(skip)]
+    pub deltas: Array2<f32>,
+    #[serde(skip)]
+    pub weight_gradients: Array2<f32>,
+    #[serde(skip)]
+    pub bias_gradients: Array1<f32>,
+    #[serde(skip)]
+    pub slope_gradients: Array1<f32>,
+}
+
+impl Dense {
+    pub fn new<R: Rng + ?Sized>(num_neurons: usize, num_inputs: usize, activation: Option<Activation>, rng: &mut R) -> Self {
+        Self::with_init(num_neurons, num_inputs, activation, Initializer::Auto, Initializer::Auto, rng)
+    }
+
+    /// Like `new`, drawing weights and biases from the given initializers.
+    pub fn with_init<R: Rng + ?Sized>(
+        num_neurons: usize,
+        num_inputs: usize,
+        activation: Option<Activation>,
+        weight_init: Initializer,
+        bias_init: Initializer,
+        rng: &mut R,
+    ) -> Self {
+        let (weights, biases) = match activation {
+            Some(activation) if num_inputs > 0 => {
+                let weights = weight_init.sample((num_neurons, num_inputs), num_inputs, num_neurons, activation, rng);
+                let biases = bias_init.sample((1, num_neurons), num_inputs, num_neurons, activation, rng);
+                (weights, biases.remove_axis(Axis(0)))
+            }
+            _ => (Array2::zeros((num_neurons, 0)), Array1::zeros(num_neurons)),
+        };
+        Self::from_parameters(weights, biases, activation)
+    }
+
+    pub fn from_parameters(weights: Array2<f32>, biases: Array1<f32>, activation: Option<Activation>) -> Self {
+        let (num_neurons, num_inputs) = weights.dim();
+        let slopes = match activation {
+            Some(Activation::PReLU(initial_slope)) => Array1::from_elem(num_neurons, initial_slope),
+            _ => Array1::zeros(0),
+        };
+        Dense {
+            weights,
+            biases,
+            activation,
+            slope_gradients: Array1::zeros(slopes.len()),
+            slopes,
+            inputs: Array2::zeros((0, num_inputs)),
+            raw_values: Array2::zeros((0, num_neurons)),
+            activated_values: Array2::zeros((0, num_neurons)),
+            deltas: Array2::zeros((0, num_neurons)),
+            weight_gradients: Array2::zeros((num_neurons, num_inputs)),
+            bias_gradients: Array1::zeros(num_neurons),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.weights.nrows()
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.weights.ncols()
+    }
+
+    pub fn is_input(&self) -> bool {
+        self.num_inputs() == 0
+    }
+
+    /// Forward pass; `inputs` holds one sample per row.
+    pub fn forward(&mut self, inputs: &Array2<f32>) -> Array2<f32> {
+        let raw = if self.is_input() {
+            inputs.clone()
+        } else {
+            inputs.dot(&self.weights.t()) + &self.biases
+        };
+
+        self.inputs = inputs.clone();
+        self.activated_values = self.activate(&raw);
+        self.raw_values = raw;
+        self.activated_values.clone()
+    }
+
+    /// Like `forward`, but leaves the caches untouched so it can run on a
+    /// shared layer.
+    pub fn infer(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        if self.is_input() {
+            return self.activate(inputs);
+        }
+        self.activate(&(inputs.dot(&self.weights.t()) + &self.biases))
+    }
+
+    /// Backward pass for the batch cached by `forward`. `grad_output` is the
+    /// gradient of the loss with respect to this layer's activated values.
+    /// Fills the parameter gradients, averaged over the batch, and returns the
+    /// gradient with respect to this layer's inputs.
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let deltas = self.activation_backward(grad_output);
+        self.backward_deltas(deltas)
+    }
+
+    /// Like `backward`, but starting from the gradient with respect to this
+    /// layer's raw values.
+    pub fn backward_deltas(&mut self, deltas: Array2<f32>) -> Array2<f32> {
+        let batch_size = deltas.nrows().max(1) as f32;
+        self.weight_gradients = deltas.t().dot(&self.inputs) / batch_size;
+        self.bias_gradients = deltas.sum_axis(Axis(0)) / batch_size;
+        let grad_input = deltas.dot(&self.weights);
+        self.deltas = deltas;
+        grad_input
+    }
+
+    /// Trainable parameters paired with their gradients from the last backward
+    /// pass, weights first. The input layer has none.
+    pub fn parameters_mut(&mut self) -> Vec<(ArrayViewMutD<'_, f32>, ArrayViewD<'_, f32>)> {
+        if self.is_input() {
+            return Vec::new();
+        }
+        let mut parameters = vec![
+            (self.weights.view_mut().into_dyn(), self.weight_gradients.view().into_dyn()),
+            (self.biases.view_mut().into_dyn(), self.bias_gradients.view().into_dyn()),
+        ];
+        if !self.slopes.is_empty() {
+            parameters.push((self.slopes.view_mut().into_dyn(), self.slope_gradients.view().into_dyn()));
+        }
+        parameters
+    }
+
+    fn activate(&self, raw: &Array2<f32>) -> Array2<f32> {
+        match self.activation {
+            Some(Activation::Softmax) => {
+                let mut activated = raw.clone();
+                for mut row in activated.rows_mut() {
+                    row.assign(&Array1::from(softmax(&row.to_owned())));
+                }
+                activated
+            }
+            Some(Activation::PReLU(_)) => {
+                let mut activated = raw.clone();
+                for mut row in activated.rows_mut() {
+                    row.zip_mut_with(&self.slopes, |z, &slope| {
+                        if *z <= 0.0 {
+                            *z *= slope;
+                        }
+                    });
+                }
+                activated
+            }
+            Some(activation) => raw.mapv(|z| activation.activate(z)),
+            None => raw.clone(),
+        }
+    }
+
+    /// Maps the gradient with respect to the activated values to the gradient
+    /// with respect to the raw values, using the cached forward values. For
+    /// PReLU this also fills `slope_gradients`, averaged over the batch.
+    fn activation_backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        match self.activation {
+            // softmax mixes every output of a sample, so apply its Jacobian:
+            // dL/dz = s * (g - sum(g * s))
+            Some(Activation::Softmax) => {
+                let mut deltas = grad_output.clone();
+                for (mut delta, s) in deltas.rows_mut().into_iter().zip(self.activated_values.rows()) {
+                    let dot = delta.dot(&s);
+                    delta.zip_mut_with(&s, |g, &s| *g = s * (*g - dot));
+                }
+                deltas
+            }
+            Some(Activation::PReLU(_)) => {
+                let negative_part = self.raw_values.mapv(|z| z.min(0.0));
+                let batch_size = grad_output.nrows().max(1) as f32;
+                self.slope_gradients = (grad_output * &negative_part).sum_axis(Axis(0)) / batch_size;
+
+                let mut deltas = grad_output.clone();
+                for (mut delta, raw) in deltas.rows_mut().into_iter().zip(self.raw_values.rows()) {
+                    for ((g, &z), &slope) in delta.iter_mut().zip(raw.iter()).zip(self.slopes.iter()) {
+                        if z <= 0.0 {
+                            *g *= slope;
+                        }
+                    }
+                }
+                deltas
+            }
+            Some(activation) => {
+                let mut deltas = grad_output.clone();
+                Zip::from(&mut deltas)
+                    .and(&self.raw_values)
+                    .and(&self.activated_values)
+                    .for_each(|g, &z, &a| *g *= activation.derivate(z, a));
+                deltas
+            }
+            None => grad_output.clone(),
+        }
+    }
+}
+
+/// On-disk layouts a `Dense` layer can be read from. Models saved before
+/// layers were matrix-backed store one `Neuron` per row and are converted on load.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DenseRepr {
+    Dense {
+        weights: Array2<f32>,
+        biases: Array1<f32>,
+        activation: Option<Activation>,
+        #[serde(default)]
+        slopes: Option<Array1<f32>>,
+    },
+    Legacy {
+        neurons: Vec<Neuron>,
+        activation: Option<Activation>,
+    },
+}
+
+impl TryFrom<DenseRepr> for Dense {
+    type Error = String;
+
+    fn try_from(repr: DenseRepr) -> Result<Self, Self::Error> {
+        match repr {
+            DenseRepr::Dense { weights, biases, activation, slopes } => {
+                if weights.nrows() != biases.len() {
+                    return Err(format!(
+                        "layer has {} weight rows but {} biases",
+                        weights.nrows(),
+                        biases.len()
+                    ));
+                }
+                let mut layer = Dense::from_parameters(weights, biases, activation);
+                if let Some(slopes) = slopes {
+                    if slopes.len() != layer.slopes.len() {
+                        return Err(format!(
+                            "layer has {} PReLU slopes but expected {}",
+                            slopes.len(),
+                            layer.slopes.len()
+                        ));
+                    }
+                    layer.slopes = slopes;
+                }
+                Ok(layer)
+            }
+            DenseRepr::Legacy { neurons, activation } => {
+                let num_inputs = neurons.first().map_or(0, |n| n.weights.len());
+                let flat: Vec<f32> = neurons.iter().flat_map(|n| n.weights.iter().copied()).collect();
+                let weights = Array2::from_shape_vec((neurons.len(), num_inputs), flat)
+                    .map_err(|_| "legacy layer has neurons with differing input counts".to_string())?;
+                let biases = neurons.iter().map(|n| n.bias).collect();
+                Ok(Dense::from_parameters(weights, biases, activation))
+            }
+        }
+    }
+}
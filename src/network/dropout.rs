@@ -0,0 +1,49 @@
+use crate::network::layer::Mode;
+use ndarray::Array2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Inverted dropout: in training mode every value is zeroed with probability
+/// `rate` and the survivors are scaled by `1 / (1 - rate)`, so evaluation needs
+/// no rescaling and passes values through unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dropout {
+    pub rate: f32,
+    size: usize,
+
+    /// Scale applied to each value by the last forward pass (`0` for dropped
+    /// values), reused by `backward`.
+    #[serde(skip)]
+    pub mask: Array2<f32>,
+}
+
+impl Dropout {
+    /// Dropout over `size` values.
+    pub fn new(size: usize, rate: f32) -> Self {
+        Dropout {
+            rate,
+            size,
+            mask: Array2::zeros((0, size)),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn forward<R: Rng + ?Sized>(&mut self, inputs: &Array2<f32>, mode: Mode, rng: &mut R) -> Array2<f32> {
+        self.mask = match mode {
+            Mode::Train if self.rate > 0.0 => {
+                let scale = 1.0 / (1.0 - self.rate);
+                let rate = self.rate;
+                Array2::from_shape_simple_fn(inputs.dim(), || if rng.gen::<f32>() < rate { 0.0 } else { scale })
+            }
+            _ => Array2::ones(inputs.dim()),
+        };
+        inputs * &self.mask
+    }
+
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        grad_output * &self.mask
+    }
+}
@@ -0,0 +1,24 @@
+use crate::network::conv::{shape_len, ImageShape};
+use serde::{Deserialize, Serialize};
+
+/// Marks where image-shaped values become a plain vector, e.g. before the
+/// first dense layer after a convolution. Batches already hold every sample as
+/// one flat row, so values pass through unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flatten {
+    input_shape: ImageShape,
+}
+
+impl Flatten {
+    pub fn new(input_shape: ImageShape) -> Self {
+        Flatten { input_shape }
+    }
+
+    pub fn input_shape(&self) -> ImageShape {
+        self.input_shape
+    }
+
+    pub fn size(&self) -> usize {
+        shape_len(self.input_shape)
+    }
+}
@@ -0,0 +1,160 @@
+use crate::network::activation::Activation;
+use ndarray::Array2;
+use ndarray_rand::rand_distr::{StandardNormal, Uniform};
+use ndarray_rand::RandomExt;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a layer's weights or biases are initialized. `fan_in` is the number of
+/// inputs of the layer and `fan_out` its number of neurons.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Initializer {
+    /// Uniform in `±sqrt(2 / fan_in)` for rectifiers and `±sqrt(1 / fan_in)`
+    /// otherwise, for weights and biases alike. This is how layers were always
+    /// initialized before initializers could be chosen.
+    #[default]
+    Auto,
+    /// Glorot: uniform in `±sqrt(6 / (fan_in + fan_out))`.
+    XavierUniform,
+    /// Glorot: normal with std `sqrt(2 / (fan_in + fan_out))`.
+    XavierNormal,
+    /// Kaiming: uniform in `±sqrt(6 / fan_in)`.
+    HeUniform,
+    /// Kaiming: normal with std `sqrt(2 / fan_in)`.
+    HeNormal,
+    /// Uniform in `±sqrt(3 / fan_in)`.
+    LeCunUniform,
+    /// Normal with std `sqrt(1 / fan_in)`.
+    LeCunNormal,
+    /// A random (semi-)orthogonal matrix scaled by the gain.
+    Orthogonal(f32),
+    /// Normal with the given std, redrawing values beyond two standard deviations.
+    TruncatedNormal(f32),
+    Zeros,
+    Constant(f32),
+}
+
+/// Truncated normals are redrawn beyond this many standard deviations.
+const TRUNCATION: f32 = 2.0;
+
+impl Initializer {
+    /// Draws a `(rows, cols)` matrix for a layer with the given fan-in, fan-out
+    /// and activation. Biases are drawn as a single row.
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        (rows, cols): (usize, usize),
+        fan_in: usize,
+        fan_out: usize,
+        activation: Activation,
+        rng: &mut R,
+    ) -> Array2<f32> {
+        let shape = (rows, cols);
+        let fan_in = fan_in.max(1) as f32;
+        let fan_avg = (fan_in + fan_out.max(1) as f32) / 2.0;
+        match *self {
+            Initializer::Auto => {
+                let scale = if activation.is_rectifier() { (2.0 / fan_in).sqrt() } else { (1.0 / fan_in).sqrt() };
+                uniform(shape, scale, rng)
+            }
+            Initializer::XavierUniform => uniform(shape, (3.0 / fan_avg).sqrt(), rng),
+            Initializer::XavierNormal => normal(shape, (1.0 / fan_avg).sqrt(), rng),
+            Initializer::HeUniform => uniform(shape, (6.0 / fan_in).sqrt(), rng),
+            Initializer::HeNormal => normal(shape, (2.0 / fan_in).sqrt(), rng),
+            Initializer::LeCunUniform => uniform(shape, (3.0 / fan_in).sqrt(), rng),
+            Initializer::LeCunNormal => normal(shape, (1.0 / fan_in).sqrt(), rng),
+            Initializer::Orthogonal(gain) => orthogonal(shape, rng) * gain,
+            Initializer::TruncatedNormal(std) => Array2::from_shape_simple_fn(shape, || loop {
+                let z: f32 = rng.sample(StandardNormal);
+                if z.abs() <= TRUNCATION {
+                    return z * std;
+                }
+            }),
+            Initializer::Zeros => Array2::zeros(shape),
+            Initializer::Constant(value) => Array2::from_elem(shape, value),
+        }
+    }
+}
+
+fn uniform<R: Rng + ?Sized>(shape: (usize, usize), scale: f32, rng: &mut R) -> Array2<f32> {
+    Array2::random_using(shape, Uniform::new(-scale, scale), rng)
+}
+
+fn normal<R: Rng + ?Sized>(shape: (usize, usize), std: f32, rng: &mut R) -> Array2<f32> {
+    Array2::random_using(shape, StandardNormal, rng) * std
+}
+
+/// Orthonormalizes a standard normal matrix with modified Gram-Schmidt. When
+/// `rows < cols` the rows are orthonormal, otherwise the columns are.
+fn orthogonal<R: Rng + ?Sized>((rows, cols): (usize, usize), rng: &mut R) -> Array2<f32> {
+    let mut q = normal((rows.max(cols), rows.min(cols)), 1.0, rng);
+    for j in 0..q.ncols() {
+        for k in 0..j {
+            let projection = q.column(j).dot(&q.column(k));
+            let basis = q.column(k).to_owned();
+            q.column_mut(j).scaled_add(-projection, &basis);
+        }
+        let norm = q.column(j).dot(&q.column(j)).sqrt().max(f32::EPSILON);
+        q.column_mut(j).mapv_inplace(|x| x / norm);
+    }
+    if rows < cols {
+        q.reversed_axes().as_standard_layout().into_owned()
+    } else {
+        q
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseInitializerError(pub String);
+
+impl fmt::Display for ParseInitializerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown initializer '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseInitializerError {}
+
+/// Parses names like `he_normal`, `glorot_uniform`, `orthogonal(1.4)` or
+/// `constant(0.1)`, ignoring case. `orthogonal`, `truncated_normal` and
+/// `constant` take an optional parameter (gain, std and value).
+impl FromStr for Initializer {
+    type Err = ParseInitializerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseInitializerError(s.to_string());
+        let lower = s.trim().to_lowercase();
+        let (name, param) = match lower.split_once('(') {
+            Some((name, rest)) => {
+                let value = rest.strip_suffix(')').ok_or_else(err)?;
+                (name.trim().to_string(), Some(value.trim().parse::<f32>().map_err(|_| err())?))
+            }
+            None => (lower, None),
+        };
+
+        let initializer = match name.replace(['_', '-'], "").as_str() {
+            "auto" => Initializer::Auto,
+            "xavieruniform" | "glorotuniform" => Initializer::XavierUniform,
+            "xaviernormal" | "glorotnormal" => Initializer::XavierNormal,
+            "heuniform" | "kaiminguniform" => Initializer::HeUniform,
+            "henormal" | "kaimingnormal" => Initializer::HeNormal,
+            "lecununiform" => Initializer::LeCunUniform,
+            "lecunnormal" => Initializer::LeCunNormal,
+            "orthogonal" => Initializer::Orthogonal(param.unwrap_or(1.0)),
+            "truncatednormal" => Initializer::TruncatedNormal(param.unwrap_or(0.05)),
+            "zeros" | "zero" => Initializer::Zeros,
+            "constant" => Initializer::Constant(param.unwrap_or(0.0)),
+            _ => return Err(err()),
+        };
+
+        let takes_param = matches!(
+            initializer,
+            Initializer::Orthogonal(_) | Initializer::TruncatedNormal(_) | Initializer::Constant(_)
+        );
+        if param.is_some() && !takes_param {
+            return Err(err());
+        }
+        Ok(initializer)
+    }
+}
@@ -1,24 +1,403 @@
 use crate::network::activation::Activation;
-use crate::network::neuron::Neuron;
-use ndarray::Array1;
+use crate::network::conv::{shape_len, Conv2d, ImageShape};
+use crate::network::dense::Dense;
+use crate::network::dropout::Dropout;
+use crate::network::flatten::Flatten;
+use crate::network::normalization::{BatchNorm, LayerNorm};
+use crate::network::pooling::{AvgPool2d, GlobalAvgPool, MaxPool2d};
+use ndarray::{Array2, ArrayViewD, ArrayViewMutD};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+/// Whether a forward pass is part of training. `Dropout` only acts in `Train`
+/// mode and `BatchNorm` uses batch statistics there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Train,
+    Eval,
+}
+
+/// One layer of a `Network`. Every variant works on batches with one sample
+/// per row and caches what its backward pass needs. Spatial layers read each
+/// row as an image of their `ImageShape`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Layer {
-    pub neurons: Vec<Neuron>,
-    pub activation: Option<Activation>,
+#[serde(from = "LayerRepr")]
+#[allow(clippy::large_enum_variant)] // a network holds only a handful of layers
+pub enum Layer {
+    Dense(Dense),
+    Dropout(Dropout),
+    BatchNorm(BatchNorm),
+    LayerNorm(LayerNorm),
+    Conv2d(Conv2d),
+    MaxPool2d(MaxPool2d),
+    AvgPool2d(AvgPool2d),
+    GlobalAvgPool(GlobalAvgPool),
+    Flatten(Flatten),
 }
 
 impl Layer {
-    pub fn new(num_neurons: usize, num_inputs: usize, activation: Option<Activation>) -> Self {
-        let neurons = (0..num_neurons)
-            .map(|_| Neuron::new(num_inputs, activation.as_ref()))
-            .collect();
-        Layer { neurons, activation }
+    /// Number of values this layer outputs per sample.
+    pub fn size(&self) -> usize {
+        match self {
+            Layer::Dense(layer) => layer.size(),
+            Layer::Dropout(layer) => layer.size(),
+            Layer::BatchNorm(layer) => layer.size(),
+            Layer::LayerNorm(layer) => layer.size(),
+            Layer::Conv2d(layer) => layer.size(),
+            Layer::MaxPool2d(layer) => layer.size(),
+            Layer::AvgPool2d(layer) => layer.size(),
+            Layer::GlobalAvgPool(layer) => layer.size(),
+            Layer::Flatten(layer) => layer.size(),
+        }
+    }
+
+    /// Number of values this layer expects per sample; `0` for the input layer.
+    pub fn num_inputs(&self) -> usize {
+        match self {
+            Layer::Dense(layer) => layer.num_inputs(),
+            _ => self.input_shape().map_or(self.size(), shape_len),
+        }
+    }
+
+    /// Shape spatial layers read their inputs as.
+    pub fn input_shape(&self) -> Option<ImageShape> {
+        match self {
+            Layer::Conv2d(layer) => Some(layer.input_shape()),
+            Layer::MaxPool2d(layer) => Some(layer.input_shape()),
+            Layer::AvgPool2d(layer) => Some(layer.input_shape()),
+            Layer::GlobalAvgPool(layer) => Some(layer.input_shape()),
+            Layer::Flatten(layer) => Some(layer.input_shape()),
+            _ => None,
+        }
+    }
+
+    /// Shape of the outputs of layers that produce images.
+    pub fn output_shape(&self) -> Option<ImageShape> {
+        match self {
+            Layer::Conv2d(layer) => Some(layer.output_shape()),
+            Layer::MaxPool2d(layer) => Some(layer.output_shape()),
+            Layer::AvgPool2d(layer) => Some(layer.output_shape()),
+            _ => None,
+        }
+    }
+
+    pub fn is_input(&self) -> bool {
+        matches!(self, Layer::Dense(layer) if layer.is_input())
+    }
+
+    pub fn activation(&self) -> Option<Activation> {
+        match self {
+            Layer::Dense(layer) => layer.activation,
+            Layer::Conv2d(layer) => Some(layer.activation),
+            _ => None,
+        }
+    }
+
+    /// Short name used by `Network::summary`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Layer::Dense(layer) if layer.is_input() => "Input",
+            Layer::Dense(_) => "Dense",
+            Layer::Dropout(_) => "Dropout",
+            Layer::BatchNorm(_) => "BatchNorm",
+            Layer::LayerNorm(_) => "LayerNorm",
+            Layer::Conv2d(_) => "Conv2d",
+            Layer::MaxPool2d(_) => "MaxPool2d",
+            Layer::AvgPool2d(_) => "AvgPool2d",
+            Layer::GlobalAvgPool(_) => "GlobalAvgPool",
+            Layer::Flatten(_) => "Flatten",
+        }
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        match self {
+            Layer::Dense(layer) if layer.is_input() => 0,
+            Layer::Dense(layer) => layer.weights.len() + layer.biases.len() + layer.slopes.len(),
+            Layer::BatchNorm(layer) => layer.gamma.len() + layer.beta.len(),
+            Layer::LayerNorm(layer) => layer.gamma.len() + layer.beta.len(),
+            Layer::Conv2d(layer) => layer.weights.len() + layer.biases.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn forward<R: Rng + ?Sized>(&mut self, inputs: &Array2<f32>, mode: Mode, rng: &mut R) -> Array2<f32> {
+        match self {
+            Layer::Dense(layer) => layer.forward(inputs),
+            Layer::Dropout(layer) => layer.forward(inputs, mode, rng),
+            Layer::BatchNorm(layer) => layer.forward(inputs, mode),
+            Layer::LayerNorm(layer) => layer.forward(inputs),
+            Layer::Conv2d(layer) => layer.forward(inputs),
+            Layer::MaxPool2d(layer) => layer.forward(inputs),
+            Layer::AvgPool2d(layer) => layer.forward(inputs),
+            Layer::GlobalAvgPool(layer) => layer.forward(inputs),
+            Layer::Flatten(_) => inputs.clone(),
+        }
+    }
+
+    /// Evaluation-mode forward pass that leaves the caches untouched.
+    pub fn infer(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        match self {
+            Layer::Dense(layer) => layer.infer(inputs),
+            Layer::Dropout(_) | Layer::Flatten(_) => inputs.clone(),
+            Layer::BatchNorm(layer) => layer.infer(inputs),
+            Layer::LayerNorm(layer) => layer.infer(inputs),
+            Layer::Conv2d(layer) => layer.infer(inputs),
+            Layer::MaxPool2d(layer) => layer.infer(inputs),
+            Layer::AvgPool2d(layer) => layer.forward(inputs),
+            Layer::GlobalAvgPool(layer) => layer.forward(inputs),
+        }
+    }
+
+    /// Maps the gradient with respect to this layer's outputs to the gradient
+    /// with respect to its inputs, filling parameter gradients on the way.
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        match self {
+            Layer::Dense(layer) => layer.backward(grad_output),
+            Layer::Dropout(layer) => layer.backward(grad_output),
+            Layer::BatchNorm(layer) => layer.backward(grad_output),
+            Layer::LayerNorm(layer) => layer.backward(grad_output),
+            Layer::Conv2d(layer) => layer.backward(grad_output),
+            Layer::MaxPool2d(layer) => layer.backward(grad_output),
+            Layer::AvgPool2d(layer) => layer.backward(grad_output),
+            Layer::GlobalAvgPool(layer) => layer.backward(grad_output),
+            Layer::Flatten(_) => grad_output.clone(),
+        }
+    }
+
+    /// Whether this layer has a weight matrix: dense layers other than the
+    /// input and convolutions.
+    pub fn has_weights(&self) -> bool {
+        match self {
+            Layer::Dense(layer) => !layer.is_input(),
+            Layer::Conv2d(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Parameters that regularization applies to: the weights, plus biases,
+    /// PReLU slopes and normalization scales and shifts when `all` is set.
+    pub fn regularized_parameters(&self, all: bool) -> Vec<ArrayViewD<'_, f32>> {
+        let mut parameters = Vec::new();
+        match self {
+            Layer::Dense(layer) if !layer.is_input() => {
+                parameters.push(layer.weights.view().into_dyn());
+                if all {
+                    parameters.push(layer.biases.view().into_dyn());
+                    parameters.push(layer.slopes.view().into_dyn());
+                }
+            }
+            Layer::Conv2d(layer) => {
+                parameters.push(layer.weights.view().into_dyn());
+                if all {
+                    parameters.push(layer.biases.view().into_dyn());
+                }
+            }
+            Layer::BatchNorm(layer) if all => {
+                parameters.push(layer.gamma.view().into_dyn());
+                parameters.push(layer.beta.view().into_dyn());
+            }
+            Layer::LayerNorm(layer) if all => {
+                parameters.push(layer.gamma.view().into_dyn());
+                parameters.push(layer.beta.view().into_dyn());
+            }
+            _ => {}
+        }
+        parameters
+    }
+
+    /// Like `regularized_parameters`, paired with mutable gradients from the
+    /// last backward pass.
+    pub fn regularized_parameters_mut(&mut self, all: bool) -> Vec<(ArrayViewMutD<'_, f32>, ArrayViewMutD<'_, f32>)> {
+        let mut parameters = Vec::new();
+        match self {
+            Layer::Dense(layer) if !layer.is_input() => {
+                parameters.push((layer.weights.view_mut().into_dyn(), layer.weight_gradients.view_mut().into_dyn()));
+                if all {
+                    parameters.push((layer.biases.view_mut().into_dyn(), layer.bias_gradients.view_mut().into_dyn()));
+                    parameters.push((layer.slopes.view_mut().into_dyn(), layer.slope_gradients.view_mut().into_dyn()));
+                }
+            }
+            Layer::Conv2d(layer) => {
+                parameters.push((layer.weights.view_mut().into_dyn(), layer.weight_gradients.view_mut().into_dyn()));
+                if all {
+                    parameters.push((layer.biases.view_mut().into_dyn(), layer.bias_gradients.view_mut().into_dyn()));
+                }
+            }
+            Layer::BatchNorm(layer) if all => {
+                parameters.push((layer.gamma.view_mut().into_dyn(), layer.gamma_gradients.view_mut().into_dyn()));
+                parameters.push((layer.beta.view_mut().into_dyn(), layer.beta_gradients.view_mut().into_dyn()));
+            }
+            Layer::LayerNorm(layer) if all => {
+                parameters.push((layer.gamma.view_mut().into_dyn(), layer.gamma_gradients.view_mut().into_dyn()));
+                parameters.push((layer.beta.view_mut().into_dyn(), layer.beta_gradients.view_mut().into_dyn()));
+            }
+            _ => {}
+        }
+        parameters
     }
 
-    pub fn activated_values(&self) -> Array1<f32> {
-        Array1::from_iter(self.neurons.iter().map(|n| n.activated_value))
+    /// Gradients from the last backward pass, in the order of `parameters_mut`.
+    pub fn gradients_mut(&mut self) -> Vec<ArrayViewMutD<'_, f32>> {
+        self.regularized_parameters_mut(true).into_iter().map(|(_, grad)| grad).collect()
+    }
+
+    /// Trainable parameters paired with their gradients from the last backward pass.
+    pub fn parameters_mut(&mut self) -> Vec<(ArrayViewMutD<'_, f32>, ArrayViewD<'_, f32>)> {
+        match self {
+            Layer::Dense(layer) => layer.parameters_mut(),
+            Layer::BatchNorm(layer) => layer.parameters_mut(),
+            Layer::LayerNorm(layer) => layer.parameters_mut(),
+            Layer::Conv2d(layer) => layer.parameters_mut(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Layers are stored tagged with their kind. Models saved before there was
+/// more than one kind hold bare dense layers.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LayerRepr {
+    Tagged(TaggedLayer),
+    Legacy(Dense),
+}
+
+#[derive(Deserialize)]
+#[allow(clippy::large_enum_variant)]
+enum TaggedLayer {
+    Dense(Dense),
+    Dropout(Dropout),
+    BatchNorm(BatchNorm),
+    LayerNorm(LayerNorm),
+    Conv2d(Conv2d),
+    MaxPool2d(MaxPool2d),
+    AvgPool2d(AvgPool2d),
+    GlobalAvgPool(GlobalAvgPool),
+    Flatten(Flatten),
+}
+
+impl From<LayerRepr> for Layer {
+    fn from(repr: LayerRepr) -> Self {
+        match repr {
+            LayerRepr::Tagged(TaggedLayer::Dense(layer)) | LayerRepr::Legacy(layer) => Layer::Dense(layer),
+            LayerRepr::Tagged(TaggedLayer::Dropout(layer)) => Layer::Dropout(layer),
+            LayerRepr::Tagged(TaggedLayer::BatchNorm(layer)) => Layer::BatchNorm(layer),
+            LayerRepr::Tagged(TaggedLayer::LayerNorm(layer)) => Layer::LayerNorm(layer),
+            LayerRepr::Tagged(TaggedLayer::Conv2d(layer)) => Layer::Conv2d(layer),
+            LayerRepr::Tagged(TaggedLayer::MaxPool2d(layer)) => Layer::MaxPool2d(layer),
+            LayerRepr::Tagged(TaggedLayer::AvgPool2d(layer)) => Layer::AvgPool2d(layer),
+            LayerRepr::Tagged(TaggedLayer::GlobalAvgPool(layer)) => Layer::GlobalAvgPool(layer),
+            LayerRepr::Tagged(TaggedLayer::Flatten(layer)) => Layer::Flatten(layer),
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::conv::Window;
+    use crate::network::initializer::Initializer;
+    use crate::utils::math::seeded_rng;
+    use ndarray::{ArrayD, Axis};
+    use rand::Rng;
+
+    const STEP: f32 = 1e-2;
+
+    fn random(shape: (usize, usize), rng: &mut impl Rng) -> Array2<f32> {
+        Array2::from_shape_fn(shape, |_| rng.gen_range(-1.0..1.0))
+    }
+
+    /// `sum(outputs * weights)`, the objective the gradients are checked on.
+    fn objective(layer: &mut Layer, inputs: &Array2<f32>, weights: &Array2<f32>) -> f32 {
+        (layer.forward(inputs, Mode::Train, &mut seeded_rng(Some(0))) * weights).sum()
+    }
+
+    /// Compares the input and parameter gradients of `layer.backward` with
+    /// central differences. Parameter gradients are averaged over the batch,
+    /// so they are scaled back up before comparing.
+    fn check_gradients(mut layer: Layer, inputs: Array2<f32>) {
+        let mut rng = seeded_rng(Some(1));
+        let weights = random((inputs.nrows(), layer.size()), &mut rng);
+        layer.forward(&inputs, Mode::Train, &mut rng);
+        let grad_input = layer.backward(&weights);
+        let batch_size = inputs.nrows() as f32;
+        let grad_params: Vec<ArrayD<f32>> = layer.gradients_mut().iter().map(|grad| grad.to_owned() * batch_size).collect();
+
+        let close = |analytic: f32, numeric: f32, what: String| {
+            let tolerance = 2e-2 * analytic.abs().max(numeric.abs()).max(1.0);
+            assert!((analytic - numeric).abs() <= tolerance, "{}: analytic {} vs numeric {}", what, analytic, numeric);
+        };
+        for (i, &analytic) in grad_input.iter().enumerate() {
+            let mut shifted = inputs.clone();
+            let index = (i / inputs.ncols(), i % inputs.ncols());
+            shifted[index] += STEP;
+            let plus = objective(&mut layer, &shifted, &weights);
+            shifted[index] -= 2.0 * STEP;
+            let minus = objective(&mut layer, &shifted, &weights);
+            close(analytic, (plus - minus) / (2.0 * STEP), format!("{} input {:?}", layer.kind(), index));
+        }
+        for (p, grad) in grad_params.iter().enumerate() {
+            for (j, &analytic) in grad.iter().enumerate() {
+                let nudge = |layer: &mut Layer, by: f32| {
+                    *layer.regularized_parameters_mut(true)[p].0.iter_mut().nth(j).unwrap() += by;
+                };
+                nudge(&mut layer, STEP);
+                let plus = objective(&mut layer, &inputs, &weights);
+                nudge(&mut layer, -2.0 * STEP);
+                let minus = objective(&mut layer, &inputs, &weights);
+                nudge(&mut layer, STEP);
+                close(analytic, (plus - minus) / (2.0 * STEP), format!("{} parameter {}[{}]", layer.kind(), p, j));
+            }
+        }
+    }
+
+    #[test]
+    fn dense_gradients() {
+        let mut rng = seeded_rng(Some(2));
+        for activation in [Activation::Tanh, Activation::Sigmoid, Activation::PReLU(0.2)] {
+            let layer = Dense::new(3, 4, Some(activation), &mut rng);
+            check_gradients(Layer::Dense(layer), random((3, 4), &mut rng));
+        }
+    }
+
+    #[test]
+    fn conv2d_gradients() {
+        let mut rng = seeded_rng(Some(3));
+        for window in [Window::new(3), Window::new(2).stride(2).padding(1), Window::new(2).dilation(2)] {
+            let layer = Conv2d::new(
+                (2, 5, 5),
+                3,
+                window,
+                Activation::Tanh,
+                Initializer::Auto,
+                Initializer::Auto,
+                &mut rng,
+            );
+            check_gradients(Layer::Conv2d(layer), random((2, 50), &mut rng));
+        }
+    }
+
+    #[test]
+    fn pooling_gradients() {
+        let mut rng = seeded_rng(Some(4));
+        for window in [Window::new(2).stride(2), Window::new(3).padding(1)] {
+            // values far enough apart that a nudge never changes which one is largest
+            let inputs = Array2::from_shape_fn((2, 32), |(i, j)| ((i * 32 + j) * 37 % 64) as f32 / 8.0);
+            check_gradients(Layer::MaxPool2d(MaxPool2d::new((2, 4, 4), window)), inputs);
+            check_gradients(Layer::AvgPool2d(AvgPool2d::new((2, 4, 4), window)), random((2, 32), &mut rng));
+        }
+        check_gradients(Layer::GlobalAvgPool(GlobalAvgPool::new((2, 3, 3))), random((2, 18), &mut rng));
+    }
+
+    #[test]
+    fn normalization_gradients() {
+        let mut rng = seeded_rng(Some(5));
+        let mut batch_norm = BatchNorm::new(4);
+        batch_norm.gamma = random((1, 4), &mut rng).remove_axis(Axis(0)) + 1.0;
+        check_gradients(Layer::BatchNorm(batch_norm), random((5, 4), &mut rng) * 2.0);
+
+        let mut layer_norm = LayerNorm::new(6);
+        layer_norm.beta = random((1, 6), &mut rng).remove_axis(Axis(0));
+        check_gradients(Layer::LayerNorm(layer_norm), random((3, 6), &mut rng) * 2.0);
+    }
+}
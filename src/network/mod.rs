@@ -1,25 +1,39 @@
 
 pub mod neuron;
 pub mod layer;
+pub mod dense;
+pub mod dropout;
+pub mod normalization;
+pub mod conv;
+pub mod pooling;
+pub mod flatten;
 pub mod activation;
+pub mod initializer;
+pub mod model;
 
-use crate::network::activation::Activation;
-use crate::network::layer::Layer;
-
-pub fn initialize_network(layer_sizes: &[usize], activations: &[Activation]) -> Vec<Layer> {
-    let mut layers = Vec::new();
-
-    // input layer
-    layers.push(Layer::new(layer_sizes[0], 0, None));
+pub use model::{Network, NetworkBuilder};
 
-    // hidden layers
-    for i in 1..layer_sizes.len() - 1 {
-        layers.push(Layer::new(layer_sizes[i], layer_sizes[i - 1], Some(activations[i - 1])));
+use crate::error::{NeuralNetError, Result};
+use crate::network::activation::Activation;
+use rand::Rng;
+
+/// Builds a network with `layer_sizes[0]` inputs and one dense layer for every
+/// other size, using `activations[i]` for dense layer `i`. Weights are drawn from `rng`.
+pub fn initialize_network<R: Rng + ?Sized>(
+    layer_sizes: &[usize],
+    activations: &[Activation],
+    rng: &mut R,
+) -> Result<Network> {
+    let (&input, dense) = layer_sizes.split_first().ok_or_else(|| {
+        NeuralNetError::InvalidConfig("at least two layers required (input and output)".to_string())
+    })?;
+    if activations.len() != dense.len() {
+        return Err(NeuralNetError::shape_mismatch("activations", dense.len(), activations.len()));
     }
 
-    // output layer
-    layers.push(Layer::new(layer_sizes[layer_sizes.len() - 1], layer_sizes[layer_sizes.len() - 2], Some(Activation::Softmax)));
-
-    layers
+    dense
+        .iter()
+        .zip(activations)
+        .fold(Network::builder().input(input), |builder, (&size, &activation)| builder.dense(size, activation))
+        .build_with_rng(rng)
 }
-
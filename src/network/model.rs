@@ -0,0 +1,446 @@
+use crate::data::dataset::Sample;
+use crate::error::{NeuralNetError, Result};
+use crate::network::activation::Activation;
+use crate::network::conv::{Conv2d, ImageShape, Window};
+use crate::network::initializer::Initializer;
+use crate::network::dense::Dense;
+use crate::network::dropout::Dropout;
+use crate::network::flatten::Flatten;
+use crate::network::layer::{Layer, Mode};
+use crate::network::normalization::{BatchNorm, LayerNorm};
+use crate::network::pooling::{AvgPool2d, GlobalAvgPool, MaxPool2d};
+use crate::utils::math::{is_finite, seeded_rng};
+use ndarray::{Array1, Array2, ArrayBase, Axis, Data, Dimension};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// A feed-forward network: an input layer followed by layers whose shapes are
+/// known to fit together, ending in a dense output layer.
+///
+/// Serialized as its list of layers, so model files stay plain arrays of layers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Layer>", into = "Vec<Layer>")]
+pub struct Network {
+    layers: Vec<Layer>,
+}
+
+impl Network {
+    pub fn builder() -> NetworkBuilder {
+        NetworkBuilder::default()
+    }
+
+    /// Wraps existing layers, checking that they start with an input layer, end
+    /// with a dense layer, and that every layer takes as many inputs as the
+    /// previous one outputs.
+    pub fn from_layers(layers: Vec<Layer>) -> Result<Self> {
+        if layers.len() < 2 {
+            return Err(NeuralNetError::shape_mismatch("number of layers", 2, layers.len()));
+        }
+        if !layers[0].is_input() {
+            return Err(NeuralNetError::shape_mismatch("inputs of the input layer", 0, layers[0].num_inputs()));
+        }
+        if !matches!(layers[layers.len() - 1], Layer::Dense(_)) {
+            return Err(NeuralNetError::InvalidConfig("the output layer must be a dense layer".to_string()));
+        }
+        for layer in &layers {
+            if let Layer::Dropout(dropout) = layer {
+                check_dropout_rate(dropout.rate)?;
+            }
+        }
+        for (i, pair) in layers.windows(2).enumerate() {
+            if pair[1].is_input() || pair[1].num_inputs() != pair[0].size() {
+                return Err(NeuralNetError::shape_mismatch(
+                    format!("inputs of layer {}", i + 1),
+                    pair[0].size(),
+                    pair[1].num_inputs(),
+                ));
+            }
+        }
+        Ok(Network { layers })
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [Layer] {
+        &mut self.layers
+    }
+
+    pub fn into_layers(self) -> Vec<Layer> {
+        self.layers
+    }
+
+    pub fn input_size(&self) -> usize {
+        self.layers[0].size()
+    }
+
+    pub fn output_size(&self) -> usize {
+        self.layers[self.layers.len() - 1].size()
+    }
+
+    /// Number of trainable parameters: weights, biases, PReLU slopes and
+    /// normalization scales and shifts.
+    pub fn parameter_count(&self) -> usize {
+        self.layers.iter().map(Layer::parameter_count).sum()
+    }
+
+    /// Runs a single sample through the network, caching every layer's values
+    /// for back-propagation. `rng` drives the random layers in `Mode::Train`.
+    /// `inputs` may be flat or image-shaped and is read in row-major order.
+    pub fn forward<S, D, R>(&mut self, inputs: &ArrayBase<S, D>, mode: Mode, rng: &mut R) -> Result<Array1<f32>>
+    where
+        S: Data<Elem = f32>,
+        D: Dimension,
+        R: Rng + ?Sized,
+    {
+        let outputs = self.forward_batch(&as_row(inputs), mode, rng)?;
+        Ok(outputs.row(0).to_owned())
+    }
+
+    /// Batched version of `forward`; `inputs` holds one sample per row.
+    pub fn forward_batch<R: Rng + ?Sized>(&mut self, inputs: &Array2<f32>, mode: Mode, rng: &mut R) -> Result<Array2<f32>> {
+        self.check_input(inputs.ncols())?;
+        Ok(self.layers.iter_mut().fold(inputs.clone(), |activations, layer| layer.forward(&activations, mode, rng)))
+    }
+
+    /// Like `forward_batch`, but also returns the index of the first layer
+    /// whose outputs contain NaN or infinity.
+    pub fn forward_batch_checked<R: Rng + ?Sized>(
+        &mut self,
+        inputs: &Array2<f32>,
+        mode: Mode,
+        rng: &mut R,
+    ) -> Result<(Array2<f32>, Option<usize>)> {
+        self.check_input(inputs.ncols())?;
+        let mut non_finite = None;
+        let mut activations = inputs.clone();
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            activations = layer.forward(&activations, mode, rng);
+            if non_finite.is_none() && !is_finite(&activations) {
+                non_finite = Some(index);
+            }
+        }
+        Ok((activations, non_finite))
+    }
+
+    /// Output activations for a single sample in `Mode::Eval`, without
+    /// touching the caches.
+    pub fn predict_proba<S: Data<Elem = f32>, D: Dimension>(&self, inputs: &ArrayBase<S, D>) -> Result<Array1<f32>> {
+        let outputs = self.predict_proba_batch(&as_row(inputs))?;
+        Ok(outputs.row(0).to_owned())
+    }
+
+    /// Batched version of `predict_proba`; `inputs` holds one sample per row.
+    pub fn predict_proba_batch(&self, inputs: &Array2<f32>) -> Result<Array2<f32>> {
+        self.check_input(inputs.ncols())?;
+        Ok(self.layers.iter().fold(inputs.clone(), |activations, layer| layer.infer(&activations)))
+    }
+
+    /// Index of the largest output for a single sample.
+    pub fn predict<S: Data<Elem = f32>, D: Dimension>(&self, inputs: &ArrayBase<S, D>) -> Result<usize> {
+        Ok(argmax(&self.predict_proba(inputs)?))
+    }
+
+    /// Checks that every sample in `dataset` fits the network's input and output sizes.
+    pub fn check_dataset(&self, dataset: &[Sample]) -> Result<()> {
+        for (i, sample) in dataset.iter().enumerate() {
+            if sample.inputs.len() != self.input_size() {
+                return Err(NeuralNetError::shape_mismatch(
+                    format!("inputs of sample {}", i),
+                    self.input_size(),
+                    sample.inputs.len(),
+                ));
+            }
+            if sample.target.len() != self.output_size() {
+                return Err(NeuralNetError::shape_mismatch(
+                    format!("target of sample {}", i),
+                    self.output_size(),
+                    sample.target.len(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// One line per layer with its size, activation and parameter count.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{:<8}{:<14}{:<10}{:<16}{:>10}\n", "Layer", "Type", "Output", "Activation", "Params");
+        for (i, layer) in self.layers.iter().enumerate() {
+            let activation = match layer {
+                Layer::Dropout(dropout) => format!("rate {}", dropout.rate),
+                Layer::BatchNorm(norm) => format!("momentum {}", norm.momentum),
+                _ => layer.activation().map_or("-".to_string(), |a| format!("{:?}", a)),
+            };
+            let output = match layer.output_shape() {
+                Some((channels, height, width)) => format!("{}x{}x{}", channels, height, width),
+                None => layer.size().to_string(),
+            };
+            let _ = writeln!(
+                summary,
+                "{:<8}{:<14}{:<10}{:<16}{:>10}",
+                i,
+                layer.kind(),
+                output,
+                activation,
+                layer.parameter_count()
+            );
+        }
+        let _ = write!(summary, "Total params: {}", self.parameter_count());
+        summary
+    }
+
+    fn check_input(&self, found: usize) -> Result<()> {
+        if found != self.input_size() {
+            return Err(NeuralNetError::shape_mismatch("network inputs", self.input_size(), found));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<Vec<Layer>> for Network {
+    type Error = NeuralNetError;
+
+    fn try_from(layers: Vec<Layer>) -> Result<Self> {
+        Network::from_layers(layers)
+    }
+}
+
+impl From<Network> for Vec<Layer> {
+    fn from(network: Network) -> Self {
+        network.layers
+    }
+}
+
+/// Builds a `Network` layer by layer:
+///
+/// `Network::builder().input(784).dense(128, Activation::ReLU).dropout(0.2).dense(10, Activation::Softmax).build()`
+///
+/// Convolutional networks start from `input_shape` and flatten before their
+/// dense layers:
+///
+/// `Network::builder().input_shape(1, 28, 28).conv2d(6, Window::new(5), Activation::ReLU).max_pool2d(2, 2).flatten().dense(10, Activation::Softmax).build()`
+#[derive(Debug, Clone, Default)]
+pub struct NetworkBuilder {
+    input: Option<usize>,
+    shape: Option<ImageShape>,
+    layers: Vec<LayerSpec>,
+}
+
+#[derive(Debug, Clone)]
+enum LayerSpec {
+    Dense { size: usize, activation: Activation, weight_init: Initializer, bias_init: Initializer },
+    Dropout(f32),
+    BatchNorm,
+    LayerNorm,
+    Conv2d { channels: usize, window: Window, activation: Activation, weight_init: Initializer, bias_init: Initializer },
+    MaxPool2d(Window),
+    AvgPool2d(Window),
+    GlobalAvgPool,
+    Flatten,
+}
+
+impl NetworkBuilder {
+    pub fn input(mut self, size: usize) -> Self {
+        self.input = Some(size);
+        self.shape = None;
+        self
+    }
+
+    /// Image inputs of `channels x height x width` values, for networks that
+    /// start with spatial layers.
+    pub fn input_shape(mut self, channels: usize, height: usize, width: usize) -> Self {
+        self.input = Some(channels * height * width);
+        self.shape = Some((channels, height, width));
+        self
+    }
+
+    pub fn dense(self, size: usize, activation: Activation) -> Self {
+        self.dense_with_init(size, activation, Initializer::Auto, Initializer::Auto)
+    }
+
+    /// Adds a dense layer whose weights and biases are drawn from the given initializers.
+    pub fn dense_with_init(
+        mut self,
+        size: usize,
+        activation: Activation,
+        weight_init: Initializer,
+        bias_init: Initializer,
+    ) -> Self {
+        self.layers.push(LayerSpec::Dense { size, activation, weight_init, bias_init });
+        self
+    }
+
+    /// Adds inverted dropout with the given rate over the previous layer's outputs.
+    pub fn dropout(mut self, rate: f32) -> Self {
+        self.layers.push(LayerSpec::Dropout(rate));
+        self
+    }
+
+    /// Adds batch normalization over the previous layer's outputs.
+    pub fn batch_norm(mut self) -> Self {
+        self.layers.push(LayerSpec::BatchNorm);
+        self
+    }
+
+    /// Adds layer normalization over the previous layer's outputs.
+    pub fn layer_norm(mut self) -> Self {
+        self.layers.push(LayerSpec::LayerNorm);
+        self
+    }
+
+    /// Adds a convolution with `channels` kernels. The activation must be
+    /// element-wise, so neither `Softmax` nor `PReLU`.
+    pub fn conv2d(self, channels: usize, window: Window, activation: Activation) -> Self {
+        self.conv2d_with_init(channels, window, activation, Initializer::Auto, Initializer::Auto)
+    }
+
+    /// Adds a convolution whose kernels and biases are drawn from the given initializers.
+    pub fn conv2d_with_init(
+        mut self,
+        channels: usize,
+        window: Window,
+        activation: Activation,
+        weight_init: Initializer,
+        bias_init: Initializer,
+    ) -> Self {
+        self.layers.push(LayerSpec::Conv2d { channels, window, activation, weight_init, bias_init });
+        self
+    }
+
+    pub fn max_pool2d(mut self, size: usize, stride: usize) -> Self {
+        self.layers.push(LayerSpec::MaxPool2d(Window::new(size).stride(stride)));
+        self
+    }
+
+    pub fn avg_pool2d(mut self, size: usize, stride: usize) -> Self {
+        self.layers.push(LayerSpec::AvgPool2d(Window::new(size).stride(stride)));
+        self
+    }
+
+    /// Averages every channel to a single value, giving flat outputs.
+    pub fn global_avg_pool(mut self) -> Self {
+        self.layers.push(LayerSpec::GlobalAvgPool);
+        self
+    }
+
+    /// Turns image-shaped outputs into flat ones, as dense layers need.
+    pub fn flatten(mut self) -> Self {
+        self.layers.push(LayerSpec::Flatten);
+        self
+    }
+
+    /// Creates the layers with weights drawn from an unseeded generator; see
+    /// `build_with_rng` for reproducible weights.
+    pub fn build(self) -> Result<Network> {
+        self.build_with_rng(&mut seeded_rng(None))
+    }
+
+    /// Creates the layers with freshly initialized weights drawn from `rng`.
+    /// Fails without an input size, with an empty layer, with a dropout rate
+    /// outside `[0, 1)`, when the last layer is not dense, or when spatial and
+    /// dense layers are mixed without flattening in between.
+    pub fn build_with_rng<R: Rng + ?Sized>(self, rng: &mut R) -> Result<Network> {
+        let input = self
+            .input
+            .ok_or_else(|| NeuralNetError::InvalidConfig("the network needs an input size".to_string()))?;
+        if !matches!(self.layers.last(), Some(LayerSpec::Dense { .. })) {
+            return Err(NeuralNetError::InvalidConfig("the network must end with a dense layer".to_string()));
+        }
+        let empty_layer = self.layers.iter().any(|spec| {
+            matches!(spec, LayerSpec::Dense { size: 0, .. } | LayerSpec::Conv2d { channels: 0, .. })
+        });
+        if input == 0 || empty_layer {
+            return Err(NeuralNetError::InvalidConfig("every layer needs at least one neuron".to_string()));
+        }
+
+        let mut layers = vec![Layer::Dense(Dense::new(input, 0, None, rng))];
+        let mut num_inputs = input;
+        let mut shape = self.shape;
+        for spec in self.layers {
+            let layer = match spec {
+                LayerSpec::Dense { size, activation, weight_init, bias_init } => {
+                    if shape.is_some() {
+                        return Err(NeuralNetError::InvalidConfig(
+                            "dense layers need flat inputs; add flatten or global_avg_pool before them".to_string(),
+                        ));
+                    }
+                    Layer::Dense(Dense::with_init(size, num_inputs, Some(activation), weight_init, bias_init, rng))
+                }
+                LayerSpec::Dropout(rate) => {
+                    check_dropout_rate(rate)?;
+                    Layer::Dropout(Dropout::new(num_inputs, rate))
+                }
+                LayerSpec::BatchNorm => Layer::BatchNorm(BatchNorm::new(num_inputs)),
+                LayerSpec::LayerNorm => Layer::LayerNorm(LayerNorm::new(num_inputs)),
+                LayerSpec::Conv2d { channels, window, activation, weight_init, bias_init } => {
+                    let input_shape = image_input(shape, "conv2d")?;
+                    check_window(input_shape, &window, "conv2d")?;
+                    if matches!(activation, Activation::Softmax | Activation::PReLU(_)) {
+                        return Err(NeuralNetError::InvalidConfig(format!(
+                            "conv2d needs an element-wise activation, got {:?}",
+                            activation
+                        )));
+                    }
+                    Layer::Conv2d(Conv2d::new(input_shape, channels, window, activation, weight_init, bias_init, rng))
+                }
+                LayerSpec::MaxPool2d(window) => {
+                    let input_shape = image_input(shape, "max_pool2d")?;
+                    check_window(input_shape, &window, "max_pool2d")?;
+                    Layer::MaxPool2d(MaxPool2d::new(input_shape, window))
+                }
+                LayerSpec::AvgPool2d(window) => {
+                    let input_shape = image_input(shape, "avg_pool2d")?;
+                    check_window(input_shape, &window, "avg_pool2d")?;
+                    Layer::AvgPool2d(AvgPool2d::new(input_shape, window))
+                }
+                LayerSpec::GlobalAvgPool => Layer::GlobalAvgPool(GlobalAvgPool::new(image_input(shape, "global_avg_pool")?)),
+                LayerSpec::Flatten => Layer::Flatten(Flatten::new(image_input(shape, "flatten")?)),
+            };
+            num_inputs = layer.size();
+            // dropout and normalization keep the shape of their inputs
+            if !matches!(layer, Layer::Dropout(_) | Layer::BatchNorm(_) | Layer::LayerNorm(_)) {
+                shape = layer.output_shape();
+            }
+            layers.push(layer);
+        }
+        Network::from_layers(layers)
+    }
+}
+
+fn check_dropout_rate(rate: f32) -> Result<()> {
+    if !(0.0..1.0).contains(&rate) {
+        return Err(NeuralNetError::InvalidConfig(format!("dropout rate must be in [0, 1), got {}", rate)));
+    }
+    Ok(())
+}
+
+fn image_input(shape: Option<ImageShape>, layer: &str) -> Result<ImageShape> {
+    shape.ok_or_else(|| {
+        NeuralNetError::InvalidConfig(format!("{} needs image inputs; start the network with input_shape", layer))
+    })
+}
+
+fn check_window((_, height, width): ImageShape, window: &Window, layer: &str) -> Result<()> {
+    if window.output_dims(height, width).is_none() {
+        return Err(NeuralNetError::InvalidConfig(format!(
+            "{} window of size {} (stride {}, padding {}, dilation {}) does not fit a {}x{} input",
+            layer, window.size, window.stride, window.padding, window.dilation, height, width
+        )));
+    }
+    Ok(())
+}
+
+/// One sample as a batch of one, flattened in row-major order.
+fn as_row<S: Data<Elem = f32>, D: Dimension>(inputs: &ArrayBase<S, D>) -> Array2<f32> {
+    Array1::from_iter(inputs.iter().copied()).insert_axis(Axis(0))
+}
+
+fn argmax(vals: &Array1<f32>) -> usize {
+    vals.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
@@ -0,0 +1,300 @@
+use crate::network::layer::Mode;
+use crate::utils::math::is_finite;
+use ndarray::{Array1, Array2, ArrayViewD, ArrayViewMutD, Axis};
+use serde::{Deserialize, Serialize};
+
+/// Which normalization `Config` inserts after every hidden layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Normalization {
+    #[default]
+    None,
+    BatchNorm,
+    LayerNorm,
+}
+
+impl Normalization {
+    pub const ALL: [Normalization; 3] = [Normalization::None, Normalization::BatchNorm, Normalization::LayerNorm];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalization::None => "None",
+            Normalization::BatchNorm => "Batch Norm",
+            Normalization::LayerNorm => "Layer Norm",
+        }
+    }
+}
+
+const DEFAULT_MOMENTUM: f32 = 0.1;
+const DEFAULT_EPSILON: f32 = 1e-5;
+
+/// Batch normalization over the features of a batch: each feature is
+/// normalized with the batch mean and variance in `Mode::Train` and with the
+/// running estimates in `Mode::Eval`, then scaled by `gamma` and shifted by `beta`.
+///
+/// A training batch of one sample has no variance, so it is normalized with
+/// the running estimates instead and leaves them unchanged. A batch with
+/// non-finite statistics leaves them unchanged too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "BatchNormRepr")]
+pub struct BatchNorm {
+    pub gamma: Array1<f32>,
+    pub beta: Array1<f32>,
+    pub running_mean: Array1<f32>,
+    pub running_var: Array1<f32>,
+    /// Weight of the current batch when updating the running estimates.
+    pub momentum: f32,
+    pub epsilon: f32,
+
+    #[serde(skip)]
+    pub normalized: Array2<f32>,
+    #[serde(skip)]
+    pub inv_std: Array1<f32>,
+    /// Whether the last forward pass used batch statistics, which makes the
+    /// backward pass account for the mean and variance depending on the inputs.
+    #[serde(skip)]
+    pub used_batch_stats: bool,
+    #[serde(skip)]
+    pub gamma_gradients: Array1<f32>,
+    #[serde(skip)]
+    pub beta_gradients: Array1<f32>,
+}
+
+impl BatchNorm {
+    pub fn new(size: usize) -> Self {
+        BatchNorm {
+            gamma: Array1::ones(size),
+            beta: Array1::zeros(size),
+            running_mean: Array1::zeros(size),
+            running_var: Array1::ones(size),
+            momentum: DEFAULT_MOMENTUM,
+            epsilon: DEFAULT_EPSILON,
+            normalized: Array2::zeros((0, size)),
+            inv_std: Array1::zeros(size),
+            used_batch_stats: false,
+            gamma_gradients: Array1::zeros(size),
+            beta_gradients: Array1::zeros(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.gamma.len()
+    }
+
+    pub fn forward(&mut self, inputs: &Array2<f32>, mode: Mode) -> Array2<f32> {
+        let batch_size = inputs.nrows();
+        self.used_batch_stats = mode == Mode::Train && batch_size > 1;
+        let (mean, var) = if self.used_batch_stats {
+            let mean = inputs.sum_axis(Axis(0)) / batch_size as f32;
+            let var = (inputs - &mean).mapv(|x| x * x).sum_axis(Axis(0)) / batch_size as f32;
+            // running variance is the unbiased estimate
+            if is_finite(&var) {
+                let unbiased = &var * (batch_size as f32 / (batch_size - 1) as f32);
+                self.running_mean = &self.running_mean * (1.0 - self.momentum) + &mean * self.momentum;
+                self.running_var = &self.running_var * (1.0 - self.momentum) + unbiased * self.momentum;
+            }
+            (mean, var)
+        } else {
+            (self.running_mean.clone(), self.running_var.clone())
+        };
+
+        self.inv_std = var.mapv(|v| 1.0 / (v + self.epsilon).sqrt());
+        self.normalized = (inputs - &mean) * &self.inv_std;
+        &self.normalized * &self.gamma + &self.beta
+    }
+
+    /// Evaluation-mode forward pass that leaves the caches untouched.
+    pub fn infer(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        let inv_std = self.running_var.mapv(|v| 1.0 / (v + self.epsilon).sqrt());
+        (inputs - &self.running_mean) * &inv_std * &self.gamma + &self.beta
+    }
+
+    /// Parameter gradients are averaged over the batch.
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let batch_size = grad_output.nrows().max(1) as f32;
+        self.gamma_gradients = (grad_output * &self.normalized).sum_axis(Axis(0)) / batch_size;
+        self.beta_gradients = grad_output.sum_axis(Axis(0)) / batch_size;
+
+        let scale = &self.gamma * &self.inv_std;
+        if !self.used_batch_stats {
+            return grad_output * &scale;
+        }
+        // dx = gamma * inv_std * (g - mean(g) - x_hat * mean(g * x_hat))
+        let centered = grad_output - &self.beta_gradients - &self.normalized * &self.gamma_gradients;
+        centered * &scale
+    }
+
+    pub fn parameters_mut(&mut self) -> Vec<(ArrayViewMutD<'_, f32>, ArrayViewD<'_, f32>)> {
+        vec![
+            (self.gamma.view_mut().into_dyn(), self.gamma_gradients.view().into_dyn()),
+            (self.beta.view_mut().into_dyn(), self.beta_gradients.view().into_dyn()),
+        ]
+    }
+}
+
+/// Layer normalization: each sample is normalized over its own features, then
+/// scaled by `gamma` and shifted by `beta`. Behaves the same in training and
+/// evaluation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "LayerNormRepr")]
+pub struct LayerNorm {
+    pub gamma: Array1<f32>,
+    pub beta: Array1<f32>,
+    pub epsilon: f32,
+
+    #[serde(skip)]
+    pub normalized: Array2<f32>,
+    /// One entry per sample of the last batch.
+    #[serde(skip)]
+    pub inv_std: Array1<f32>,
+    #[serde(skip)]
+    pub gamma_gradients: Array1<f32>,
+    #[serde(skip)]
+    pub beta_gradients: Array1<f32>,
+}
+
+impl LayerNorm {
+    pub fn new(size: usize) -> Self {
+        LayerNorm {
+            gamma: Array1::ones(size),
+            beta: Array1::zeros(size),
+            epsilon: DEFAULT_EPSILON,
+            normalized: Array2::zeros((0, size)),
+            inv_std: Array1::zeros(0),
+            gamma_gradients: Array1::zeros(size),
+            beta_gradients: Array1::zeros(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.gamma.len()
+    }
+
+    pub fn forward(&mut self, inputs: &Array2<f32>) -> Array2<f32> {
+        let (normalized, inv_std) = self.normalize(inputs);
+        self.normalized = normalized;
+        self.inv_std = inv_std;
+        &self.normalized * &self.gamma + &self.beta
+    }
+
+    /// Forward pass that leaves the caches untouched.
+    pub fn infer(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        let (normalized, _) = self.normalize(inputs);
+        normalized * &self.gamma + &self.beta
+    }
+
+    /// Parameter gradients are averaged over the batch.
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let batch_size = grad_output.nrows().max(1) as f32;
+        self.gamma_gradients = (grad_output * &self.normalized).sum_axis(Axis(0)) / batch_size;
+        self.beta_gradients = grad_output.sum_axis(Axis(0)) / batch_size;
+
+        // per sample: dx = inv_std * (h - mean(h) - x_hat * mean(h * x_hat)), h = g * gamma
+        let size = self.size().max(1) as f32;
+        let scaled = grad_output * &self.gamma;
+        let mean = (scaled.sum_axis(Axis(1)) / size).insert_axis(Axis(1));
+        let projection = ((&scaled * &self.normalized).sum_axis(Axis(1)) / size).insert_axis(Axis(1));
+        (scaled - mean - &self.normalized * projection) * self.inv_std.view().insert_axis(Axis(1))
+    }
+
+    pub fn parameters_mut(&mut self) -> Vec<(ArrayViewMutD<'_, f32>, ArrayViewD<'_, f32>)> {
+        vec![
+            (self.gamma.view_mut().into_dyn(), self.gamma_gradients.view().into_dyn()),
+            (self.beta.view_mut().into_dyn(), self.beta_gradients.view().into_dyn()),
+        ]
+    }
+
+    /// Returns the normalized inputs and the inverse standard deviation of each row.
+    fn normalize(&self, inputs: &Array2<f32>) -> (Array2<f32>, Array1<f32>) {
+        let size = inputs.ncols().max(1) as f32;
+        let mean = (inputs.sum_axis(Axis(1)) / size).insert_axis(Axis(1));
+        let centered = inputs - &mean;
+        let var = centered.mapv(|x| x * x).sum_axis(Axis(1)) / size;
+        let inv_std = var.mapv(|v| 1.0 / (v + self.epsilon).sqrt());
+        (centered * inv_std.view().insert_axis(Axis(1)), inv_std)
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchNormRepr {
+    gamma: Array1<f32>,
+    beta: Array1<f32>,
+    running_mean: Array1<f32>,
+    running_var: Array1<f32>,
+    momentum: f32,
+    epsilon: f32,
+}
+
+impl TryFrom<BatchNormRepr> for BatchNorm {
+    type Error = String;
+
+    fn try_from(repr: BatchNormRepr) -> Result<Self, Self::Error> {
+        let size = repr.gamma.len();
+        for (name, len) in [
+            ("beta", repr.beta.len()),
+            ("running_mean", repr.running_mean.len()),
+            ("running_var", repr.running_var.len()),
+        ] {
+            if len != size {
+                return Err(format!("batch norm layer has {} gamma values but {} {} values", size, len, name));
+            }
+        }
+        Ok(BatchNorm {
+            gamma: repr.gamma,
+            beta: repr.beta,
+            running_mean: repr.running_mean,
+            running_var: repr.running_var,
+            momentum: repr.momentum,
+            epsilon: repr.epsilon,
+            ..BatchNorm::new(size)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct LayerNormRepr {
+    gamma: Array1<f32>,
+    beta: Array1<f32>,
+    epsilon: f32,
+}
+
+impl TryFrom<LayerNormRepr> for LayerNorm {
+    type Error = String;
+
+    fn try_from(repr: LayerNormRepr) -> Result<Self, Self::Error> {
+        let size = repr.gamma.len();
+        if repr.beta.len() != size {
+            return Err(format!("layer norm layer has {} gamma values but {} beta values", size, repr.beta.len()));
+        }
+        Ok(LayerNorm {
+            gamma: repr.gamma,
+            beta: repr.beta,
+            epsilon: repr.epsilon,
+            ..LayerNorm::new(size)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn batch_norm_trains_a_single_sample_with_running_statistics() {
+        let mut norm = BatchNorm::new(3);
+        norm.running_mean = array![0.5, -1.0, 2.0];
+        norm.running_var = array![4.0, 1.0, 0.25];
+        norm.gamma = array![2.0, 1.0, -1.0];
+        let inputs = array![[1.5, 0.0, 3.0]];
+
+        let outputs = norm.forward(&inputs, Mode::Train);
+        assert!(!norm.used_batch_stats);
+        assert_eq!(norm.running_mean, array![0.5, -1.0, 2.0]);
+        assert_eq!(norm.running_var, array![4.0, 1.0, 0.25]);
+        assert_eq!(outputs, norm.infer(&inputs));
+
+        let grad_output = array![[1.0, -2.0, 0.5]];
+        let expected = &grad_output * &(&norm.gamma * &norm.inv_std);
+        assert_eq!(norm.backward(&grad_output), expected);
+    }
+}
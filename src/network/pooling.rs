@@ -0,0 +1,252 @@
+use crate::network::conv::{shape_len, ImageShape, Window};
+use ndarray::{s, Array2};
+use serde::{Deserialize, Serialize};
+
+/// Max pooling over each channel. Every output is the largest value under
+/// the window, and only that value receives its gradient.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PoolRepr")]
+pub struct MaxPool2d {
+    pub window: Window,
+    input_shape: ImageShape,
+
+    /// Input index of the value picked for every output of the last batch.
+    #[serde(skip)]
+    pub argmax: Array2<usize>,
+}
+
+impl MaxPool2d {
+    pub fn new(input_shape: ImageShape, window: Window) -> Self {
+        MaxPool2d { window, input_shape, argmax: Array2::zeros((0, 0)) }
+    }
+
+    pub fn input_shape(&self) -> ImageShape {
+        self.input_shape
+    }
+
+    pub fn output_shape(&self) -> ImageShape {
+        pooled_shape(self.input_shape, &self.window)
+    }
+
+    pub fn size(&self) -> usize {
+        shape_len(self.output_shape())
+    }
+
+    pub fn forward(&mut self, inputs: &Array2<f32>) -> Array2<f32> {
+        let (outputs, argmax) = self.pool(inputs);
+        self.argmax = argmax;
+        outputs
+    }
+
+    /// Like `forward`, but leaves the caches untouched.
+    pub fn infer(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        self.pool(inputs).0
+    }
+
+    pub fn backward(&mut self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let mut grad_input = Array2::zeros((grad_output.nrows(), shape_len(self.input_shape)));
+        for ((grad, argmax), mut grad_in) in grad_output.rows().into_iter().zip(self.argmax.rows()).zip(grad_input.rows_mut()) {
+            for (&g, &index) in grad.iter().zip(argmax.iter()) {
+                grad_in[index] += g;
+            }
+        }
+        grad_input
+    }
+
+    fn pool(&self, inputs: &Array2<f32>) -> (Array2<f32>, Array2<usize>) {
+        let mut outputs = Array2::zeros((inputs.nrows(), self.size()));
+        let mut argmax = Array2::zeros((inputs.nrows(), self.size()));
+        for ((sample, mut out), mut picked) in inputs.rows().into_iter().zip(outputs.rows_mut()).zip(argmax.rows_mut()) {
+            for_each_window(self.input_shape, &self.window, |output, indices| {
+                let best = indices
+                    .iter()
+                    .copied()
+                    .max_by(|&a, &b| sample[a].total_cmp(&sample[b]))
+                    .expect("Window::output_dims rejects windows that cover only padding");
+                out[output] = sample[best];
+                picked[output] = best;
+            });
+        }
+        (outputs, argmax)
+    }
+}
+
+/// Average pooling over each channel. Padding is not counted in the average.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PoolRepr")]
+pub struct AvgPool2d {
+    pub window: Window,
+    input_shape: ImageShape,
+}
+
+impl AvgPool2d {
+    pub fn new(input_shape: ImageShape, window: Window) -> Self {
+        AvgPool2d { window, input_shape }
+    }
+
+    pub fn input_shape(&self) -> ImageShape {
+        self.input_shape
+    }
+
+    pub fn output_shape(&self) -> ImageShape {
+        pooled_shape(self.input_shape, &self.window)
+    }
+
+    pub fn size(&self) -> usize {
+        shape_len(self.output_shape())
+    }
+
+    pub fn forward(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        let mut outputs = Array2::zeros((inputs.nrows(), self.size()));
+        for (sample, mut out) in inputs.rows().into_iter().zip(outputs.rows_mut()) {
+            for_each_window(self.input_shape, &self.window, |output, indices| {
+                let sum: f32 = indices.iter().map(|&i| sample[i]).sum();
+                out[output] = sum / indices.len().max(1) as f32;
+            });
+        }
+        outputs
+    }
+
+    pub fn backward(&self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let mut grad_input = Array2::zeros((grad_output.nrows(), shape_len(self.input_shape)));
+        for (grad, mut grad_in) in grad_output.rows().into_iter().zip(grad_input.rows_mut()) {
+            for_each_window(self.input_shape, &self.window, |output, indices| {
+                let share = grad[output] / indices.len().max(1) as f32;
+                for &i in indices {
+                    grad_in[i] += share;
+                }
+            });
+        }
+        grad_input
+    }
+}
+
+/// Averages every channel down to a single value, so the output is a plain
+/// vector with one value per channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalAvgPool {
+    input_shape: ImageShape,
+}
+
+impl GlobalAvgPool {
+    pub fn new(input_shape: ImageShape) -> Self {
+        GlobalAvgPool { input_shape }
+    }
+
+    pub fn input_shape(&self) -> ImageShape {
+        self.input_shape
+    }
+
+    pub fn size(&self) -> usize {
+        self.input_shape.0
+    }
+
+    pub fn forward(&self, inputs: &Array2<f32>) -> Array2<f32> {
+        let (channels, height, width) = self.input_shape;
+        let area = height * width;
+        let mut outputs = Array2::zeros((inputs.nrows(), channels));
+        for (sample, mut out) in inputs.rows().into_iter().zip(outputs.rows_mut()) {
+            for (c, out) in out.iter_mut().enumerate() {
+                *out = sample.slice(s![c * area..(c + 1) * area]).sum() / area.max(1) as f32;
+            }
+        }
+        outputs
+    }
+
+    pub fn backward(&self, grad_output: &Array2<f32>) -> Array2<f32> {
+        let (_, height, width) = self.input_shape;
+        let area = height * width;
+        let mut grad_input = Array2::zeros((grad_output.nrows(), shape_len(self.input_shape)));
+        for (grad, mut grad_in) in grad_output.rows().into_iter().zip(grad_input.rows_mut()) {
+            for (c, &g) in grad.iter().enumerate() {
+                grad_in.slice_mut(s![c * area..(c + 1) * area]).fill(g / area.max(1) as f32);
+            }
+        }
+        grad_input
+    }
+}
+
+fn pooled_shape((channels, height, width): ImageShape, window: &Window) -> ImageShape {
+    let (out_h, out_w) = window.output_dims(height, width).unwrap_or((0, 0));
+    (channels, out_h, out_w)
+}
+
+/// Calls `f(output, indices)` for every output of a pooling layer, where
+/// `indices` are the input indices under its window.
+fn for_each_window(shape: ImageShape, window: &Window, mut f: impl FnMut(usize, &[usize])) {
+    let (channels, height, width) = shape;
+    let (_, out_h, out_w) = pooled_shape(shape, window);
+    let mut indices = Vec::with_capacity(window.size * window.size);
+    for c in 0..channels {
+        for oy in 0..out_h {
+            for ox in 0..out_w {
+                indices.clear();
+                for ki in 0..window.size {
+                    for kj in 0..window.size {
+                        if let Some((y, x)) = window.tap((height, width), (oy, ox), (ki, kj)) {
+                            indices.push((c * height + y) * width + x);
+                        }
+                    }
+                }
+                f((c * out_h + oy) * out_w + ox, &indices);
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PoolRepr {
+    window: Window,
+    input_shape: ImageShape,
+}
+
+impl PoolRepr {
+    fn check(&self, layer: &str) -> Result<(), String> {
+        let (_, height, width) = self.input_shape;
+        match self.window.output_dims(height, width) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} window {:?} does not fit a {}x{} input", layer, self.window, height, width)),
+        }
+    }
+}
+
+impl TryFrom<PoolRepr> for MaxPool2d {
+    type Error = String;
+
+    fn try_from(repr: PoolRepr) -> Result<Self, Self::Error> {
+        repr.check("max_pool2d")?;
+        Ok(MaxPool2d::new(repr.input_shape, repr.window))
+    }
+}
+
+impl TryFrom<PoolRepr> for AvgPool2d {
+    type Error = String;
+
+    fn try_from(repr: PoolRepr) -> Result<Self, Self::Error> {
+        repr.check("avg_pool2d")?;
+        Ok(AvgPool2d::new(repr.input_shape, repr.window))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_windows_that_cover_only_padding() {
+        assert_eq!(Window::new(2).padding(1).output_dims(4, 4), Some((5, 5)));
+        assert_eq!(Window::new(2).padding(2).output_dims(4, 4), None);
+        // taps two apart skip the single input value of the second position
+        assert_eq!(Window::new(2).padding(1).dilation(2).output_dims(1, 1), None);
+    }
+
+    #[test]
+    fn rejects_deserialized_windows_that_do_not_fit() {
+        let pool = MaxPool2d::new((1, 4, 4), Window::new(2).stride(2));
+        let json = serde_json::to_string(&pool).unwrap();
+        assert!(serde_json::from_str::<MaxPool2d>(&json).is_ok());
+        let padded = json.replace("\"padding\":0", "\"padding\":2");
+        assert!(serde_json::from_str::<MaxPool2d>(&padded).is_err());
+        assert!(serde_json::from_str::<AvgPool2d>(&padded).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::metrics::history::EpochMetrics;
+use crate::network::layer::Layer;
+use crate::network::Network;
+use crate::training::early_stopping::EarlyStopping;
+use crate::training::optimizer::OptimizerState;
+use crate::training::scheduler::SchedulerState;
+use crate::training::stability::DivergenceGuard;
+use crate::utils::math::NetRng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// On-disk format written by Save Model. Files saved before optimizer state
+/// was stored hold only the layers.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SavedModel {
+    WithOptimizer {
+        network: Vec<Layer>,
+        optimizer: Option<OptimizerState>,
+        /// Softmax temperature fitted by temperature scaling, to apply with
+        /// `Predictions::with_temperature`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<f32>,
+    },
+    Layers(Vec<Layer>),
+}
+
+impl SavedModel {
+    /// Checks that the saved layers fit together.
+    pub fn into_parts(self) -> Result<(Network, Option<OptimizerState>, Option<f32>)> {
+        let (layers, optimizer, temperature) = match self {
+            SavedModel::WithOptimizer { network, optimizer, temperature } => (network, optimizer, temperature),
+            SavedModel::Layers(network) => (network, None, None),
+        };
+        Ok((Network::from_layers(layers)?, optimizer, temperature))
+    }
+}
+
+pub fn save_model<P: AsRef<Path>>(
+    path: P,
+    network: &Network,
+    optimizer: Option<&OptimizerState>,
+    temperature: Option<f32>,
+) -> Result<()> {
+    let saved = SavedModel::WithOptimizer {
+        network: network.layers().to_vec(),
+        optimizer: optimizer.cloned(),
+        temperature,
+    };
+    std::fs::write(path, serde_json::to_string(&saved)?)?;
+    Ok(())
+}
+
+/// Reads a model written by `save_model`, rejecting files whose layers do not
+/// fit together.
+pub fn load_model<P: AsRef<Path>>(path: P) -> Result<(Network, Option<OptimizerState>, Option<f32>)> {
+    let content = std::fs::read_to_string(path)?;
+    let saved: SavedModel = serde_json::from_str(&content)?;
+    saved.into_parts()
+}
+
+/// Everything a run needs to go on exactly where it left off: training the
+/// remaining epochs of a checkpoint gives the same network as a run that was
+/// never interrupted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub config: Config,
+    /// Number of completed epochs, i.e. the index of the next epoch.
+    pub epoch: usize,
+    pub network: Network,
+    pub optimizer: OptimizerState,
+    pub scheduler: SchedulerState,
+    pub guard: DivergenceGuard,
+    pub early_stopping: Option<EarlyStopping>,
+    /// The generator as it was after the last completed epoch.
+    pub rng: NetRng,
+    pub history: Vec<EpochMetrics>,
+}
+
+impl Checkpoint {
+    /// The state of a new run with `config` before its first epoch.
+    pub fn start(config: &Config) -> Result<Self> {
+        let mut rng = config.rng();
+        let network = config.build_network(&mut rng)?;
+        Ok(Checkpoint {
+            config: config.clone(),
+            epoch: 0,
+            network,
+            optimizer: config.optimizer.build(),
+            scheduler: config.scheduler.build(),
+            guard: DivergenceGuard::new(config.on_divergence),
+            early_stopping: config.early_stopping.map(EarlyStopping::new),
+            rng,
+            history: Vec::new(),
+        })
+    }
+}
+
+/// Writes `checkpoint` to `path`, creating its directory if needed.
+pub fn save_checkpoint<P: AsRef<Path>>(path: P, checkpoint: &Checkpoint) -> Result<()> {
+    if let Some(dir) = path.as_ref().parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string(checkpoint)?)?;
+    Ok(())
+}
+
+/// Reads a checkpoint written by `save_checkpoint`, rejecting an invalid
+/// configuration.
+pub fn load_checkpoint<P: AsRef<Path>>(path: P) -> Result<Checkpoint> {
+    let content = std::fs::read_to_string(path)?;
+    let checkpoint: Checkpoint = serde_json::from_str(&content)?;
+    checkpoint.config.validate()?;
+    Ok(checkpoint)
+}
+
+/// Where the checkpoint after `epochs` completed epochs goes, or `None` when
+/// `config` does not ask for one then.
+pub fn scheduled_checkpoint(config: &Config, epochs: usize) -> Option<PathBuf> {
+    if config.checkpoint_every == 0 || !epochs.is_multiple_of(config.checkpoint_every) {
+        return None;
+    }
+    Some(checkpoint_path(&config.checkpoint_dir, epochs))
+}
+
+/// The file in `dir` for a checkpoint after `epochs` completed epochs.
+pub fn checkpoint_path<P: AsRef<Path>>(dir: P, epochs: usize) -> PathBuf {
+    dir.as_ref().join(format!("checkpoint_epoch_{:04}.json", epochs))
+}
+
+/// The checkpoints in `dir`, oldest epoch first. A missing directory has none.
+pub fn list_checkpoints<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("checkpoint_") && name.ends_with(".json"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::dataset::random_samples;
+    use crate::network::normalization::Normalization;
+    use crate::training::optimizer::OptimizerConfig;
+    use crate::training::scheduler::{LrScheduler, SchedulerConfig};
+    use crate::training::trainer::train_epoch;
+
+    fn train_until(run: &mut Checkpoint, epochs: usize) {
+        let samples = random_samples(48, 7);
+        for epoch in run.epoch..epochs {
+            let config = run.config.clone();
+            train_epoch(&mut run.network, &samples, epoch, &config, &mut run.optimizer, &run.scheduler, &mut run.guard, &mut run.rng)
+                .unwrap();
+            run.scheduler.end_epoch(epoch as f32);
+            run.epoch = epoch + 1;
+        }
+    }
+
+    #[test]
+    fn resuming_a_checkpoint_continues_exactly() {
+        let config = Config {
+            epochs: 4,
+            batch_size: 8,
+            learning_rate: 0.01,
+            seed: None,
+            optimizer: OptimizerConfig::ALL[4],
+            scheduler: SchedulerConfig::ALL[6],
+            normalization: Normalization::BatchNorm,
+            dropout: 0.2,
+            layers: vec![784, 12, 10],
+            activations: vec!["relu".into(), "softmax".into()],
+            ..Config::default()
+        };
+        let mut straight = Checkpoint::start(&config).unwrap();
+        let mut resumed = straight.clone();
+        train_until(&mut straight, 4);
+
+        train_until(&mut resumed, 2);
+        let path = std::env::temp_dir().join(format!("neural_net_resume_{}.json", std::process::id()));
+        save_checkpoint(&path, &resumed).unwrap();
+        let mut resumed = load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        train_until(&mut resumed, 4);
+
+        assert_eq!(serde_json::to_string(&resumed).unwrap(), serde_json::to_string(&straight).unwrap());
+    }
+}
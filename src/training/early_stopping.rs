@@ -0,0 +1,138 @@
+use crate::network::Network;
+use crate::training::optimizer::OptimizerState;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The evaluation metric early stopping watches after every epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Monitor {
+    #[default]
+    Loss,
+    Accuracy,
+}
+
+impl Monitor {
+    pub const ALL: [Monitor; 2] = [Monitor::Loss, Monitor::Accuracy];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Monitor::Loss => "Loss",
+            Monitor::Accuracy => "Accuracy",
+        }
+    }
+
+    /// Whether `value` beats `best` by more than `min_delta`; lower is better
+    /// for the loss and higher for the accuracy.
+    fn improves(&self, value: f32, best: f32, min_delta: f32) -> bool {
+        match self {
+            Monitor::Loss => value < best - min_delta,
+            Monitor::Accuracy => value > best + min_delta,
+        }
+    }
+}
+
+/// Early stopping settings, as stored in `Config`. `min_delta` is in the units
+/// of the monitored metric, i.e. percentage points for the accuracy.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EarlyStoppingConfig {
+    pub monitor: Monitor,
+    pub patience: usize,
+    pub min_delta: f32,
+    /// Put the weights of the best epoch back when training ends.
+    pub restore_best: bool,
+}
+
+impl Default for EarlyStoppingConfig {
+    fn default() -> Self {
+        EarlyStoppingConfig { monitor: Monitor::Loss, patience: 3, min_delta: 0.0, restore_best: true }
+    }
+}
+
+/// Why early stopping ended a run. Epochs count from zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EarlyStop {
+    pub epoch: usize,
+    pub monitor: Monitor,
+    pub min_delta: f32,
+    pub best_epoch: usize,
+    pub best_value: f32,
+}
+
+impl fmt::Display for EarlyStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} has not improved by more than {} for {} epochs since epoch {} ({:.4})",
+            self.monitor.name().to_lowercase(),
+            self.min_delta,
+            self.epoch - self.best_epoch,
+            self.best_epoch + 1,
+            self.best_value
+        )
+    }
+}
+
+/// Stops training once the monitored metric has failed to improve by more
+/// than `min_delta` for more than `patience` epochs, keeping a copy of the
+/// network and optimizer from the best epoch so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EarlyStopping {
+    config: EarlyStoppingConfig,
+    /// Best epoch and its value of the monitored metric.
+    best: Option<(usize, f32)>,
+    best_network: Option<(Network, OptimizerState)>,
+    epochs_without_improvement: usize,
+}
+
+impl EarlyStopping {
+    pub fn new(config: EarlyStoppingConfig) -> Self {
+        EarlyStopping { config, best: None, best_network: None, epochs_without_improvement: 0 }
+    }
+
+    /// Records the evaluation of `network`, trained by `optimizer`, after
+    /// `epoch`. Returns why training should stop, or `None` to go on.
+    pub fn end_epoch(
+        &mut self,
+        epoch: usize,
+        loss: f32,
+        accuracy: f32,
+        network: &Network,
+        optimizer: &OptimizerState,
+    ) -> Option<EarlyStop> {
+        let value = match self.config.monitor {
+            Monitor::Loss => loss,
+            Monitor::Accuracy => accuracy,
+        };
+        match self.best {
+            Some((best_epoch, best)) if !self.config.monitor.improves(value, best, self.config.min_delta) => {
+                self.epochs_without_improvement += 1;
+                (self.epochs_without_improvement > self.config.patience).then_some(EarlyStop {
+                    epoch,
+                    monitor: self.config.monitor,
+                    min_delta: self.config.min_delta,
+                    best_epoch,
+                    best_value: best,
+                })
+            }
+            _ => {
+                self.best = Some((epoch, value));
+                self.epochs_without_improvement = 0;
+                if self.config.restore_best {
+                    self.best_network = Some((network.clone(), optimizer.clone()));
+                }
+                None
+            }
+        }
+    }
+
+    pub fn best_epoch(&self) -> Option<usize> {
+        self.best.map(|(epoch, _)| epoch)
+    }
+
+    /// The network and optimizer from the best epoch, when `restore_best` is set.
+    pub fn take_best(&mut self) -> Option<(usize, Network, OptimizerState)> {
+        let epoch = self.best_epoch()?;
+        self.best_network.take().map(|(network, optimizer)| (epoch, network, optimizer))
+    }
+}
@@ -0,0 +1,277 @@
+use crate::network::activation::Activation;
+use ndarray::{Array1, Array2, ArrayView1, Axis, Zip};
+use serde::{Deserialize, Serialize};
+
+const EPSILON: f32 = 1e-12;
+
+/// A loss over the output layer. Every method works on a batch with one sample
+/// per row.
+pub trait Loss {
+    /// Loss of each sample in the batch.
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32>;
+
+    /// Gradient of each sample's loss with respect to the output layer's
+    /// activated values.
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32>;
+
+    /// Gradient with respect to the output layer's raw values, for pairings
+    /// where it simplifies (softmax + cross-entropy, sigmoid + binary
+    /// cross-entropy). Going through `gradient` there would divide by
+    /// probabilities that can underflow to zero.
+    fn fused_gradient(&self, _activation: Activation, _outputs: &Array2<f32>, _targets: &Array2<f32>) -> Option<Array2<f32>> {
+        None
+    }
+}
+
+/// Loss to train with, as stored in `Config`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LossConfig {
+    #[default]
+    CrossEntropy,
+    BinaryCrossEntropy,
+    MeanSquaredError,
+    MeanAbsoluteError,
+    Huber { delta: f32 },
+    Hinge { margin: f32 },
+    Focal { gamma: f32 },
+}
+
+impl LossConfig {
+    pub const ALL: [LossConfig; 7] = [
+        LossConfig::CrossEntropy,
+        LossConfig::BinaryCrossEntropy,
+        LossConfig::MeanSquaredError,
+        LossConfig::MeanAbsoluteError,
+        LossConfig::Huber { delta: 1.0 },
+        LossConfig::Hinge { margin: 1.0 },
+        LossConfig::Focal { gamma: 2.0 },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LossConfig::CrossEntropy => "Cross-Entropy",
+            LossConfig::BinaryCrossEntropy => "Binary Cross-Entropy",
+            LossConfig::MeanSquaredError => "MSE",
+            LossConfig::MeanAbsoluteError => "MAE",
+            LossConfig::Huber { .. } => "Huber",
+            LossConfig::Hinge { .. } => "Hinge",
+            LossConfig::Focal { .. } => "Focal",
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Loss + Send + Sync> {
+        match *self {
+            LossConfig::CrossEntropy => Box::new(CrossEntropy),
+            LossConfig::BinaryCrossEntropy => Box::new(BinaryCrossEntropy),
+            LossConfig::MeanSquaredError => Box::new(MeanSquaredError),
+            LossConfig::MeanAbsoluteError => Box::new(MeanAbsoluteError),
+            LossConfig::Huber { delta } => Box::new(Huber { delta }),
+            LossConfig::Hinge { margin } => Box::new(Hinge { margin }),
+            LossConfig::Focal { gamma } => Box::new(Focal { gamma }),
+        }
+    }
+}
+
+/// Mixes one-hot targets with the uniform distribution:
+/// `targets * (1 - smoothing) + smoothing / num_classes`.
+pub fn smooth_labels(targets: &Array2<f32>, smoothing: f32) -> Array2<f32> {
+    if smoothing <= 0.0 {
+        return targets.clone();
+    }
+    let uniform = smoothing / targets.ncols().max(1) as f32;
+    targets.mapv(|t| t * (1.0 - smoothing) + uniform)
+}
+
+fn elementwise<F>(outputs: &Array2<f32>, targets: &Array2<f32>, f: F) -> Array2<f32>
+where
+    F: Fn(f32, f32) -> f32,
+{
+    Zip::from(outputs).and(targets).map_collect(|&p, &t| f(p, t))
+}
+
+/// Categorical cross-entropy, `-sum(t * ln(p))`. Expects probabilities.
+#[derive(Debug, Clone, Copy)]
+pub struct CrossEntropy;
+
+impl Loss for CrossEntropy {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        elementwise(outputs, targets, |p, t| -t * (p + EPSILON).ln()).sum_axis(Axis(1))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        elementwise(outputs, targets, |p, t| -t / (p + EPSILON))
+    }
+
+    fn fused_gradient(&self, activation: Activation, outputs: &Array2<f32>, targets: &Array2<f32>) -> Option<Array2<f32>> {
+        match activation {
+            Activation::Softmax => Some(outputs - targets),
+            _ => None,
+        }
+    }
+}
+
+/// Binary cross-entropy summed over every output, treating each as an
+/// independent probability.
+#[derive(Debug, Clone, Copy)]
+pub struct BinaryCrossEntropy;
+
+impl Loss for BinaryCrossEntropy {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        elementwise(outputs, targets, |p, t| {
+            -(t * (p + EPSILON).ln() + (1.0 - t) * (1.0 - p + EPSILON).ln())
+        })
+        .sum_axis(Axis(1))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        elementwise(outputs, targets, |p, t| (p - t) / (p * (1.0 - p) + EPSILON))
+    }
+
+    fn fused_gradient(&self, activation: Activation, outputs: &Array2<f32>, targets: &Array2<f32>) -> Option<Array2<f32>> {
+        match activation {
+            Activation::Sigmoid => Some(outputs - targets),
+            _ => None,
+        }
+    }
+}
+
+/// Mean squared error over the outputs of each sample.
+#[derive(Debug, Clone, Copy)]
+pub struct MeanSquaredError;
+
+impl Loss for MeanSquaredError {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        elementwise(outputs, targets, |p, t| (p - t) * (p - t)).sum_axis(Axis(1)) / outputs.ncols() as f32
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        let n = outputs.ncols() as f32;
+        elementwise(outputs, targets, |p, t| 2.0 * (p - t) / n)
+    }
+}
+
+/// Mean absolute error over the outputs of each sample.
+#[derive(Debug, Clone, Copy)]
+pub struct MeanAbsoluteError;
+
+impl Loss for MeanAbsoluteError {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        elementwise(outputs, targets, |p, t| (p - t).abs()).sum_axis(Axis(1)) / outputs.ncols() as f32
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        let n = outputs.ncols() as f32;
+        elementwise(outputs, targets, |p, t| {
+            if p > t {
+                1.0 / n
+            } else if p < t {
+                -1.0 / n
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+/// Huber loss: quadratic within `delta` of the target, linear beyond it.
+/// Averaged over the outputs of each sample.
+#[derive(Debug, Clone, Copy)]
+pub struct Huber {
+    pub delta: f32,
+}
+
+impl Loss for Huber {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        let delta = self.delta;
+        elementwise(outputs, targets, |p, t| {
+            let error = (p - t).abs();
+            if error <= delta {
+                0.5 * error * error
+            } else {
+                delta * (error - 0.5 * delta)
+            }
+        })
+        .sum_axis(Axis(1))
+            / outputs.ncols() as f32
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        let (delta, n) = (self.delta, outputs.ncols() as f32);
+        elementwise(outputs, targets, |p, t| (p - t).clamp(-delta, delta) / n)
+    }
+}
+
+/// Multi-class hinge loss: `sum over j != y of max(0, margin + s_j - s_y)`,
+/// where `y` is the target class (the largest target value).
+#[derive(Debug, Clone, Copy)]
+pub struct Hinge {
+    pub margin: f32,
+}
+
+impl Hinge {
+    fn target_class(targets: ArrayView1<f32>) -> usize {
+        targets
+            .iter()
+            .enumerate()
+            .fold((0, f32::NEG_INFINITY), |best, (i, &t)| if t > best.1 { (i, t) } else { best })
+            .0
+    }
+}
+
+impl Loss for Hinge {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        outputs
+            .rows()
+            .into_iter()
+            .zip(targets.rows())
+            .map(|(scores, targets)| {
+                let y = Self::target_class(targets);
+                scores
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != y)
+                    .map(|(_, &s)| (self.margin + s - scores[y]).max(0.0))
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        let mut gradient = Array2::zeros(outputs.raw_dim());
+        for ((scores, targets), mut grad) in outputs.rows().into_iter().zip(targets.rows()).zip(gradient.rows_mut()) {
+            let y = Self::target_class(targets);
+            for (j, &s) in scores.iter().enumerate() {
+                if j != y && self.margin + s - scores[y] > 0.0 {
+                    grad[j] += 1.0;
+                    grad[y] -= 1.0;
+                }
+            }
+        }
+        gradient
+    }
+}
+
+/// Focal loss, `-sum(t * (1 - p)^gamma * ln(p))`: cross-entropy that
+/// down-weights samples the network already classifies confidently.
+#[derive(Debug, Clone, Copy)]
+pub struct Focal {
+    pub gamma: f32,
+}
+
+impl Loss for Focal {
+    fn loss(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array1<f32> {
+        let gamma = self.gamma;
+        elementwise(outputs, targets, |p, t| -t * (1.0 - p).max(0.0).powf(gamma) * (p + EPSILON).ln())
+            .sum_axis(Axis(1))
+    }
+
+    fn gradient(&self, outputs: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        let gamma = self.gamma;
+        elementwise(outputs, targets, |p, t| {
+            let q = (1.0 - p).max(0.0);
+            let p = p + EPSILON;
+            let modulating = if q > 0.0 { gamma * q.powf(gamma - 1.0) * p.ln() } else { 0.0 };
+            t * (modulating - q.powf(gamma) / p)
+        })
+    }
+}
@@ -1 +1,9 @@
 pub mod trainer;
+pub mod optimizer;
+pub mod scheduler;
+pub mod loss;
+pub mod checkpoint;
+pub mod regularization;
+pub mod stability;
+pub mod early_stopping;
+pub mod parallel;
@@ -0,0 +1,286 @@
+use crate::network::layer::Layer;
+use ndarray::{ArrayD, ArrayViewD, ArrayViewMutD, Zip};
+use serde::{Deserialize, Serialize};
+
+/// An update rule applied to every parameter after the backward pass.
+///
+/// Parameters are visited in a fixed order (layer by layer, weights then
+/// biases), so `index` identifies the same parameter on every step and can be
+/// used to key per-parameter state.
+pub trait Optimizer {
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32);
+
+    /// Called once per step before any parameter is updated.
+    fn begin_step(&mut self) {}
+
+    fn step(&mut self, layers: &mut [Layer], learning_rate: f32) {
+        self.begin_step();
+        let mut index = 0;
+        for layer in layers.iter_mut() {
+            for (param, grad) in layer.parameters_mut() {
+                self.update(index, param, grad, learning_rate);
+                index += 1;
+            }
+        }
+    }
+}
+
+/// The usual `Config::weight_decay` for AdamW.
+pub const ADAMW_WEIGHT_DECAY: f32 = 0.01;
+
+/// Hyperparameters of the optimizer to train with, as stored in `Config`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum OptimizerConfig {
+    #[default]
+    Sgd,
+    Nesterov { momentum: f32 },
+    AdaGrad { epsilon: f32 },
+    RMSProp { decay: f32, epsilon: f32 },
+    Adam { beta1: f32, beta2: f32, epsilon: f32 },
+    /// Adam with the decoupled weight decay of `Config::weight_decay`, which
+    /// has to be positive.
+    AdamW { beta1: f32, beta2: f32, epsilon: f32 },
+}
+
+impl OptimizerConfig {
+    /// Every optimizer with its usual default hyperparameters.
+    pub const ALL: [OptimizerConfig; 6] = [
+        OptimizerConfig::Sgd,
+        OptimizerConfig::Nesterov { momentum: 0.9 },
+        OptimizerConfig::AdaGrad { epsilon: 1e-8 },
+        OptimizerConfig::RMSProp { decay: 0.9, epsilon: 1e-8 },
+        OptimizerConfig::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 },
+        OptimizerConfig::AdamW { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizerConfig::Sgd => "SGD",
+            OptimizerConfig::Nesterov { .. } => "SGD + Nesterov",
+            OptimizerConfig::AdaGrad { .. } => "AdaGrad",
+            OptimizerConfig::RMSProp { .. } => "RMSProp",
+            OptimizerConfig::Adam { .. } => "Adam",
+            OptimizerConfig::AdamW { .. } => "AdamW",
+        }
+    }
+
+    pub fn build(&self) -> OptimizerState {
+        match *self {
+            OptimizerConfig::Sgd => OptimizerState::Sgd(Sgd),
+            OptimizerConfig::Nesterov { momentum } => OptimizerState::Nesterov(Nesterov::new(momentum)),
+            OptimizerConfig::AdaGrad { epsilon } => OptimizerState::AdaGrad(AdaGrad::new(epsilon)),
+            OptimizerConfig::RMSProp { decay, epsilon } => OptimizerState::RMSProp(RMSProp::new(decay, epsilon)),
+            OptimizerConfig::Adam { beta1, beta2, epsilon } => {
+                OptimizerState::Adam(Adam::new(beta1, beta2, epsilon))
+            }
+            OptimizerConfig::AdamW { beta1, beta2, epsilon } => {
+                OptimizerState::AdamW(AdamW::new(beta1, beta2, epsilon))
+            }
+        }
+    }
+}
+
+/// A built optimizer together with its per-parameter state. This is what gets
+/// saved next to the model so training can resume where it left off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OptimizerState {
+    Sgd(Sgd),
+    Nesterov(Nesterov),
+    AdaGrad(AdaGrad),
+    RMSProp(RMSProp),
+    Adam(Adam),
+    AdamW(AdamW),
+}
+
+impl OptimizerState {
+    fn inner(&mut self) -> &mut dyn Optimizer {
+        match self {
+            OptimizerState::Sgd(o) => o,
+            OptimizerState::Nesterov(o) => o,
+            OptimizerState::AdaGrad(o) => o,
+            OptimizerState::RMSProp(o) => o,
+            OptimizerState::Adam(o) => o,
+            OptimizerState::AdamW(o) => o,
+        }
+    }
+}
+
+impl Optimizer for OptimizerState {
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        self.inner().update(index, param, grad, learning_rate);
+    }
+
+    fn begin_step(&mut self) {
+        self.inner().begin_step();
+    }
+}
+
+/// Returns the state slot for parameter `index`, creating zeroed slots as needed.
+fn slot<'a>(slots: &'a mut Vec<ArrayD<f32>>, index: usize, grad: &ArrayViewD<f32>) -> &'a mut ArrayD<f32> {
+    while slots.len() <= index {
+        slots.push(ArrayD::zeros(grad.raw_dim()));
+    }
+    if slots[index].shape() != grad.shape() {
+        slots[index] = ArrayD::zeros(grad.raw_dim());
+    }
+    &mut slots[index]
+}
+
+/// Plain gradient descent: `param -= lr * grad`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sgd;
+
+impl Optimizer for Sgd {
+    fn update(&mut self, _: usize, mut param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        param.scaled_add(-learning_rate, &grad);
+    }
+}
+
+/// SGD with Nesterov momentum:
+/// `v = momentum * v + grad`, `param -= lr * (grad + momentum * v)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nesterov {
+    pub momentum: f32,
+    pub velocities: Vec<ArrayD<f32>>,
+}
+
+impl Nesterov {
+    pub fn new(momentum: f32) -> Self {
+        Nesterov { momentum, velocities: Vec::new() }
+    }
+}
+
+impl Optimizer for Nesterov {
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        let momentum = self.momentum;
+        let velocity = slot(&mut self.velocities, index, &grad);
+        Zip::from(param).and(velocity).and(&grad).for_each(|p, v, &g| {
+            *v = momentum * *v + g;
+            *p -= learning_rate * (g + momentum * *v);
+        });
+    }
+}
+
+/// AdaGrad: scales each step by the root of the accumulated squared gradients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaGrad {
+    pub epsilon: f32,
+    pub accumulators: Vec<ArrayD<f32>>,
+}
+
+impl AdaGrad {
+    pub fn new(epsilon: f32) -> Self {
+        AdaGrad { epsilon, accumulators: Vec::new() }
+    }
+}
+
+impl Optimizer for AdaGrad {
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        let epsilon = self.epsilon;
+        let accumulator = slot(&mut self.accumulators, index, &grad);
+        Zip::from(param).and(accumulator).and(&grad).for_each(|p, a, &g| {
+            *a += g * g;
+            *p -= learning_rate * g / (a.sqrt() + epsilon);
+        });
+    }
+}
+
+/// RMSProp: scales each step by the root of a moving average of squared gradients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RMSProp {
+    pub decay: f32,
+    pub epsilon: f32,
+    pub mean_squares: Vec<ArrayD<f32>>,
+}
+
+impl RMSProp {
+    pub fn new(decay: f32, epsilon: f32) -> Self {
+        RMSProp { decay, epsilon, mean_squares: Vec::new() }
+    }
+}
+
+impl Optimizer for RMSProp {
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        let (decay, epsilon) = (self.decay, self.epsilon);
+        let mean_square = slot(&mut self.mean_squares, index, &grad);
+        Zip::from(param).and(mean_square).and(&grad).for_each(|p, s, &g| {
+            *s = decay * *s + (1.0 - decay) * g * g;
+            *p -= learning_rate * g / (s.sqrt() + epsilon);
+        });
+    }
+}
+
+/// Adam with bias-corrected first and second moment estimates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Adam {
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    pub step: u64,
+    pub first_moments: Vec<ArrayD<f32>>,
+    pub second_moments: Vec<ArrayD<f32>>,
+}
+
+impl Adam {
+    pub fn new(beta1: f32, beta2: f32, epsilon: f32) -> Self {
+        Adam {
+            beta1,
+            beta2,
+            epsilon,
+            step: 0,
+            first_moments: Vec::new(),
+            second_moments: Vec::new(),
+        }
+    }
+}
+
+impl Optimizer for Adam {
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+        let step = self.step.max(1) as i32;
+        let correction1 = 1.0 - beta1.powi(step);
+        let correction2 = 1.0 - beta2.powi(step);
+
+        slot(&mut self.first_moments, index, &grad);
+        slot(&mut self.second_moments, index, &grad);
+        Zip::from(param)
+            .and(&mut self.first_moments[index])
+            .and(&mut self.second_moments[index])
+            .and(&grad)
+            .for_each(|p, m, v, &g| {
+                *m = beta1 * *m + (1.0 - beta1) * g;
+                *v = beta2 * *v + (1.0 - beta2) * g * g;
+                let m_hat = *m / correction1;
+                let v_hat = *v / correction2;
+                *p -= learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+            });
+    }
+}
+
+/// Adam with decoupled weight decay. The decay is `Config::weight_decay`,
+/// which `Regularizer::decay` applies after the step to the regularized
+/// parameters only, so this step is Adam's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdamW {
+    pub adam: Adam,
+}
+
+impl AdamW {
+    pub fn new(beta1: f32, beta2: f32, epsilon: f32) -> Self {
+        AdamW { adam: Adam::new(beta1, beta2, epsilon) }
+    }
+}
+
+impl Optimizer for AdamW {
+    fn begin_step(&mut self) {
+        self.adam.begin_step();
+    }
+
+    fn update(&mut self, index: usize, param: ArrayViewMutD<f32>, grad: ArrayViewD<f32>, learning_rate: f32) {
+        self.adam.update(index, param, grad, learning_rate);
+    }
+}
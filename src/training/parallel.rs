@@ -0,0 +1,366 @@
+use crate::config::Config;
+use crate::data::dataset::Sample;
+use crate::error::{NeuralNetError, Result};
+use crate::network::layer::Layer;
+use crate::network::Network;
+use crate::training::loss::Loss;
+use crate::training::optimizer::Sgd;
+use crate::training::regularization::Regularizer;
+use crate::training::stability::{Divergence, DivergenceGuard};
+use crate::training::trainer::{apply_step, batch_gradients, finish_gradients, NonFiniteAt};
+use crate::utils::math::NetRng;
+use ndarray::{ArrayViewD, ArrayViewMutD};
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// How worker threads share training when `Config::threads` is above one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ParallelMode {
+    /// Every worker computes the gradients of a shard of each batch, and their
+    /// average goes into a single optimizer step. Runs are reproducible for a
+    /// fixed seed and thread count.
+    #[default]
+    Synchronous,
+    /// Every worker trains on whole batches of its own with SGD and adds its
+    /// updates to the shared weights without locking, so it may compute
+    /// gradients from weights other workers are changing. Runs are not
+    /// reproducible.
+    Hogwild,
+}
+
+impl ParallelMode {
+    pub const ALL: [ParallelMode; 2] = [ParallelMode::Synchronous, ParallelMode::Hogwild];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParallelMode::Synchronous => "Synchronous",
+            ParallelMode::Hogwild => "Hogwild",
+        }
+    }
+}
+
+/// A pool of `threads` workers, built once per thread count and shared by
+/// every later epoch that asks for the same count.
+fn thread_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>> {
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<rayon::ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS.get_or_init(Default::default).lock().unwrap();
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(pool.clone());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| NeuralNetError::Io(std::io::Error::other(e)))?;
+    Ok(pools.entry(threads).or_insert(Arc::new(pool)).clone())
+}
+
+/// Copies of the network that compute the gradients of shards of a batch on
+/// their own threads.
+pub(crate) struct DataParallel {
+    pool: Arc<rayon::ThreadPool>,
+    replicas: Vec<Network>,
+}
+
+impl DataParallel {
+    pub(crate) fn new(network: &Network, threads: usize) -> Result<Self> {
+        Ok(DataParallel { pool: thread_pool(threads)?, replicas: vec![network.clone(); threads] })
+    }
+
+    /// Splits `samples` into one contiguous shard per replica and leaves the
+    /// average of their gradients, weighted by shard size, in `network`.
+    /// Every replica draws its dropout masks from a generator seeded from
+    /// `rng`, and BatchNorm running statistics become the average of the
+    /// replicas', weighted the same way. Returns the first non-finite
+    /// activations or loss of any shard instead, leaving `network` untouched.
+    pub(crate) fn gradients<R: Rng + ?Sized>(
+        &mut self,
+        network: &mut Network,
+        samples: &[&Sample],
+        config: &Config,
+        loss: &(dyn Loss + Sync),
+        rng: &mut R,
+    ) -> Result<Option<NonFiniteAt>> {
+        let shard_size = samples.len().div_ceil(self.replicas.len()).max(1);
+        let seeds: Vec<u64> = self.replicas.iter().map(|_| rng.gen()).collect();
+        let shards = samples.len().div_ceil(shard_size);
+        let replicas = &mut self.replicas[..shards];
+        for replica in replicas.iter_mut() {
+            copy_weights(network, replica);
+        }
+
+        let results: Vec<(usize, Result<Option<NonFiniteAt>>)> = self.pool.install(|| {
+            replicas
+                .par_iter_mut()
+                .zip(samples.par_chunks(shard_size))
+                .zip(seeds)
+                .map(|((replica, shard), seed)| {
+                    let mut rng = NetRng::seed_from_u64(seed);
+                    (shard.len(), batch_gradients(replica, shard, config, loss, &mut rng))
+                })
+                .collect()
+        });
+        let mut shares = Vec::with_capacity(results.len());
+        for (len, result) in results {
+            if let Some(non_finite) = result? {
+                return Ok(Some(non_finite));
+            }
+            shares.push(len as f32 / samples.len() as f32);
+        }
+
+        for (index, layer) in network.layers_mut().iter_mut().enumerate() {
+            for (i, mut gradient) in layer.gradients_mut().into_iter().enumerate() {
+                gradient.fill(0.0);
+                for (replica, &share) in replicas.iter_mut().zip(&shares) {
+                    gradient.scaled_add(share, &replica.layers_mut()[index].gradients_mut()[i]);
+                }
+            }
+            if let Layer::BatchNorm(norm) = layer {
+                norm.running_mean.fill(0.0);
+                norm.running_var.fill(0.0);
+                for (replica, &share) in replicas.iter().zip(&shares) {
+                    if let Layer::BatchNorm(replica) = &replica.layers()[index] {
+                        norm.running_mean.scaled_add(share, &replica.running_mean);
+                        norm.running_var.scaled_add(share, &replica.running_var);
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Every parameter and BatchNorm running statistic of a network as `f32`
+/// bits, which Hogwild workers read and add to without locking.
+struct SharedWeights {
+    values: Vec<AtomicU32>,
+}
+
+impl SharedWeights {
+    fn new(network: &Network) -> Self {
+        let values = weights(network).iter().flat_map(|tensor| tensor.iter().map(|v| AtomicU32::new(v.to_bits()))).collect();
+        SharedWeights { values }
+    }
+
+    /// Copies the current values into `replica` and into `before`.
+    fn load(&self, replica: &mut Network, before: &mut Vec<f32>) {
+        before.clear();
+        let elements = weights_mut(replica).into_iter().flat_map(|tensor| tensor.into_iter());
+        for (element, value) in elements.zip(&self.values) {
+            *element = f32::from_bits(value.load(Ordering::Relaxed));
+            before.push(*element);
+        }
+    }
+
+    /// Adds how far every value of `replica` moved from `before`. Each value
+    /// is updated on its own with a compare-and-swap, so the changes of other
+    /// workers since `load` are kept.
+    fn add_changes(&self, replica: &Network, before: &[f32]) {
+        let elements = weights(replica).into_iter().flat_map(|tensor| tensor.into_iter());
+        for ((element, &before), value) in elements.zip(before).zip(&self.values) {
+            let change = *element - before;
+            if change != 0.0 {
+                let _ = value.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                    Some((f32::from_bits(bits) + change).to_bits())
+                });
+            }
+        }
+    }
+
+    fn store(&self, network: &mut Network) {
+        let elements = weights_mut(network).into_iter().flat_map(|tensor| tensor.into_iter());
+        for (element, value) in elements.zip(&self.values) {
+            *element = f32::from_bits(value.load(Ordering::Relaxed));
+        }
+    }
+}
+
+/// Trainable parameters of `network` followed, per BatchNorm layer, by its
+/// running mean and variance: everything training changes.
+fn weights(network: &Network) -> Vec<ArrayViewD<'_, f32>> {
+    let mut weights = Vec::new();
+    for layer in network.layers() {
+        weights.extend(layer.regularized_parameters(true));
+        if let Layer::BatchNorm(norm) = layer {
+            weights.extend([norm.running_mean.view().into_dyn(), norm.running_var.view().into_dyn()]);
+        }
+    }
+    weights
+}
+
+/// `weights`, mutably.
+fn weights_mut(network: &mut Network) -> Vec<ArrayViewMutD<'_, f32>> {
+    let mut weights = Vec::new();
+    for layer in network.layers_mut() {
+        match layer {
+            Layer::BatchNorm(norm) => weights.extend([
+                norm.gamma.view_mut().into_dyn(),
+                norm.beta.view_mut().into_dyn(),
+                norm.running_mean.view_mut().into_dyn(),
+                norm.running_var.view_mut().into_dyn(),
+            ]),
+            layer => weights.extend(layer.regularized_parameters_mut(true).into_iter().map(|(param, _)| param)),
+        }
+    }
+    weights
+}
+
+/// Copies the weights of `network` into `replica`, a clone of it, leaving
+/// the caches and gradient buffers of `replica` alone.
+fn copy_weights(network: &Network, replica: &mut Network) {
+    for (from, mut to) in weights(network).into_iter().zip(weights_mut(replica)) {
+        to.assign(&from);
+    }
+}
+
+/// What Hogwild workers share besides the weights, behind a lock taken only
+/// when a batch diverges or fails.
+struct Shared<'a> {
+    guard: &'a mut DivergenceGuard,
+    first_divergence: Option<Divergence>,
+    error: Option<NeuralNetError>,
+}
+
+/// Trains on `batches` with `config.threads` workers that each take every
+/// `threads`-th batch. A worker reads the shared weights, computes the
+/// gradients of its batch and takes a plain SGD step on its own copy of the
+/// network, then adds the change of every parameter to the shared weights,
+/// all without locks, so steps of different workers interleave. BatchNorm
+/// running statistics are merged the same way. SGD keeps no state between
+/// steps, which is why `Config::validate` allows no other optimizer here.
+/// `rates` holds the scheduled learning rate of every batch. Returns the
+/// learning rate of the last step applied, whether any was, and the first
+/// divergence of the epoch.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn hogwild_epoch<R: Rng + ?Sized>(
+    network: &mut Network,
+    batches: &[Vec<&Sample>],
+    rates: &[f32],
+    epoch: usize,
+    config: &Config,
+    regularizer: &Regularizer,
+    guard: &mut DivergenceGuard,
+    rng: &mut R,
+) -> Result<(f32, bool, Option<Divergence>)> {
+    let threads = config.threads.max(1);
+    let seeds: Vec<u64> = batches.iter().map(|_| rng.gen()).collect();
+    let loss = config.loss.build();
+    let weights = SharedWeights::new(network);
+    let lr_scale = AtomicU32::new(guard.lr_scale.to_bits());
+    let learning_rate = AtomicU32::new(config.learning_rate.to_bits());
+    let (updated, stopped) = (AtomicBool::new(false), AtomicBool::new(false));
+    let shared = Mutex::new(Shared { guard, first_divergence: None, error: None });
+
+    let template: &Network = network;
+    thread_pool(threads)?.scope(|scope| {
+        for worker in 0..threads {
+            let (weights, shared, seeds, loss) = (&weights, &shared, &seeds, loss.as_ref());
+            let (lr_scale, learning_rate, updated, stopped) = (&lr_scale, &learning_rate, &updated, &stopped);
+            scope.spawn(move |_| {
+                let mut replica = template.clone();
+                let mut before = Vec::new();
+                let fail = |e: NeuralNetError| {
+                    shared.lock().unwrap().error.get_or_insert(e);
+                    stopped.store(true, Ordering::Relaxed);
+                };
+                for step in (worker..batches.len()).step_by(threads) {
+                    if stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    weights.load(&mut replica, &mut before);
+                    let mut rng = NetRng::seed_from_u64(seeds[step]);
+                    let non_finite = match batch_gradients(&mut replica, &batches[step], config, loss, &mut rng) {
+                        Ok(non_finite) => non_finite.or_else(|| finish_gradients(&mut replica, regularizer)),
+                        Err(e) => return fail(e),
+                    };
+                    if let Some((source, layer)) = non_finite {
+                        let layer = layer.map(|index| (index, replica.layers()[index].kind().to_string()));
+                        let divergence = Divergence { source, layer, epoch, step };
+                        let mut shared = shared.lock().unwrap();
+                        shared.first_divergence.get_or_insert_with(|| divergence.clone());
+                        if let Err(e) = shared.guard.handle(divergence) {
+                            drop(shared);
+                            return fail(e);
+                        }
+                        lr_scale.store(shared.guard.lr_scale.to_bits(), Ordering::Relaxed);
+                        continue;
+                    }
+
+                    let rate = rates[step] * f32::from_bits(lr_scale.load(Ordering::Relaxed));
+                    apply_step(&mut replica, &mut Sgd, regularizer, config, rate);
+                    weights.add_changes(&replica, &before);
+                    learning_rate.store(rate.to_bits(), Ordering::Relaxed);
+                    updated.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    weights.store(network);
+    let shared = shared.into_inner().unwrap();
+    match shared.error {
+        Some(e) => Err(e),
+        None => Ok((
+            f32::from_bits(learning_rate.into_inner()),
+            updated.into_inner(),
+            shared.first_divergence,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::dataset::random_samples;
+    use crate::network::normalization::Normalization;
+    use crate::training::checkpoint::Checkpoint;
+    use crate::training::trainer::train_epoch;
+
+    fn config(threads: usize, parallel_mode: ParallelMode) -> Config {
+        Config {
+            epochs: 2,
+            batch_size: 8,
+            learning_rate: 0.05,
+            seed: Some(11),
+            normalization: Normalization::BatchNorm,
+            dropout: 0.2,
+            layers: vec![784, 12, 10],
+            activations: vec!["tanh".into(), "softmax".into()],
+            threads,
+            parallel_mode,
+            ..Config::default()
+        }
+    }
+
+    /// Trains every epoch of `config` on 50 samples, so the last batch of
+    /// each epoch is smaller than the thread count.
+    fn train(config: &Config) -> Network {
+        let samples = random_samples(50, 3);
+        let mut run = Checkpoint::start(config).unwrap();
+        for epoch in 0..config.epochs {
+            train_epoch(&mut run.network, &samples, epoch, config, &mut run.optimizer, &run.scheduler, &mut run.guard, &mut run.rng)
+                .unwrap();
+        }
+        run.network
+    }
+
+    #[test]
+    fn synchronous_training_is_deterministic() {
+        let config = config(3, ParallelMode::Synchronous);
+        let first = serde_json::to_string(&train(&config)).unwrap();
+        assert_eq!(serde_json::to_string(&train(&config)).unwrap(), first);
+    }
+
+    #[test]
+    fn hogwild_training_updates_the_weights() {
+        let config = config(3, ParallelMode::Hogwild);
+        let initial = Checkpoint::start(&config).unwrap().network;
+        let trained = train(&config);
+        let (initial, trained) = (weights(&initial), weights(&trained));
+        assert!(trained.iter().flat_map(|tensor| tensor.iter()).all(|v| v.is_finite()));
+        assert!(initial.iter().zip(&trained).any(|(a, b)| a != b));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::Config;
+use crate::error::{NeuralNetError, Result};
+use crate::network::layer::Layer;
+use crate::network::Network;
+
+/// L1 and L2 penalty coefficients of one layer. The penalty on a parameter
+/// `w` is `l1 * |w| + l2 / 2 * w^2`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Penalty {
+    pub l1: f32,
+    pub l2: f32,
+}
+
+impl Penalty {
+    fn is_zero(&self) -> bool {
+        self.l1 == 0.0 && self.l2 == 0.0
+    }
+}
+
+/// The L1/L2 penalties and decoupled weight decay from `Config`, matched to
+/// the layers of one network.
+///
+/// Only weights are regularized unless `Config::regularize_all_parameters` is
+/// set. Normalization layers then use the penalty of the layer before them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regularizer {
+    /// One entry per layer of the network.
+    penalties: Vec<Penalty>,
+    weight_decay: f32,
+    all_parameters: bool,
+}
+
+impl Regularizer {
+    /// Fails if `l1` or `l2` has neither one entry per weighted layer of
+    /// `network` nor a single entry.
+    pub fn new(config: &Config, network: &Network) -> Result<Self> {
+        let weighted = network.layers().iter().filter(|layer| layer.has_weights()).count();
+        let l1 = expand_coefficients("l1", &config.l1, weighted)?;
+        let l2 = expand_coefficients("l2", &config.l2, weighted)?;
+
+        let mut coefficients = l1.into_iter().zip(l2).map(|(l1, l2)| Penalty { l1, l2 });
+        let mut current = Penalty::default();
+        let penalties = network
+            .layers()
+            .iter()
+            .map(|layer| match layer {
+                _ if layer.has_weights() => {
+                    current = coefficients.next().unwrap_or_default();
+                    current
+                }
+                Layer::BatchNorm(_) | Layer::LayerNorm(_) => current,
+                _ => Penalty::default(),
+            })
+            .collect();
+
+        Ok(Regularizer {
+            penalties,
+            weight_decay: config.weight_decay,
+            all_parameters: config.regularize_all_parameters,
+        })
+    }
+
+    /// The penalty term added to the loss.
+    pub fn penalty(&self, network: &Network) -> f32 {
+        network
+            .layers()
+            .iter()
+            .zip(&self.penalties)
+            .filter(|(_, penalty)| !penalty.is_zero())
+            .flat_map(|(layer, penalty)| {
+                layer
+                    .regularized_parameters(self.all_parameters)
+                    .into_iter()
+                    .map(move |param| param.fold(0.0, |sum, &w| sum + penalty.l1 * w.abs() + 0.5 * penalty.l2 * w * w))
+            })
+            .fold(0.0, |total, penalty| total + penalty)
+    }
+
+    /// Adds the gradient of the penalty term to the gradients from the last
+    /// backward pass.
+    pub fn add_gradients(&self, layers: &mut [Layer]) {
+        for (layer, penalty) in layers.iter_mut().zip(&self.penalties) {
+            if penalty.is_zero() {
+                continue;
+            }
+            for (param, mut grad) in layer.regularized_parameters_mut(self.all_parameters) {
+                grad.zip_mut_with(&param, |g, &w| *g += penalty.l1 * sign(w) + penalty.l2 * w);
+            }
+        }
+    }
+
+    /// Shrinks the regularized parameters by `learning_rate * weight_decay` of
+    /// their value. Called after the optimizer step, so the decay bypasses any
+    /// gradient scaling the optimizer does.
+    pub fn decay(&self, layers: &mut [Layer], learning_rate: f32) {
+        if self.weight_decay == 0.0 {
+            return;
+        }
+        let factor = 1.0 - learning_rate * self.weight_decay;
+        for layer in layers.iter_mut() {
+            for (mut param, _) in layer.regularized_parameters_mut(self.all_parameters) {
+                param *= factor;
+            }
+        }
+    }
+}
+
+/// Expands per-layer coefficients: empty means zero everywhere and a single
+/// value applies to every layer.
+pub(crate) fn expand_coefficients(name: &str, values: &[f32], layers: usize) -> Result<Vec<f32>> {
+    match values.len() {
+        0 => Ok(vec![0.0; layers]),
+        1 => Ok(vec![values[0]; layers]),
+        len if len == layers => Ok(values.to_vec()),
+        len => Err(NeuralNetError::InvalidConfig(format!(
+            "{} needs one entry per weighted layer ({}) or a single value, got {}",
+            name, layers, len
+        ))),
+    }
+}
+
+/// Subgradient of `|w|`, taking `0` at zero.
+fn sign(w: f32) -> f32 {
+    if w > 0.0 {
+        1.0
+    } else if w < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
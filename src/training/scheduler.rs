@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Where the training loop is when it asks for a learning rate.
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduleProgress {
+    pub epoch: usize,
+    pub step: usize,
+    pub steps_per_epoch: usize,
+    pub total_epochs: usize,
+}
+
+impl ScheduleProgress {
+    /// Epochs completed so far, including the fraction of the current one.
+    pub fn epoch_fraction(&self) -> f32 {
+        self.epoch as f32 + self.step as f32 / self.steps_per_epoch.max(1) as f32
+    }
+
+    pub fn global_step(&self) -> usize {
+        self.epoch * self.steps_per_epoch + self.step
+    }
+
+    pub fn total_steps(&self) -> usize {
+        (self.total_epochs * self.steps_per_epoch).max(1)
+    }
+}
+
+/// Decides the learning rate for each optimizer step. The training loop asks
+/// before every step and reports the validation accuracy after every epoch.
+pub trait LrScheduler {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32;
+
+    fn end_epoch(&mut self, _validation_accuracy: f32) {}
+}
+
+/// Schedule to train with, as stored in `Config`. Epoch counts are in whole
+/// epochs; `OneCycle::pct_start` is a fraction of the whole run.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SchedulerConfig {
+    #[default]
+    Constant,
+    StepDecay { step_size: usize, gamma: f32 },
+    Exponential { gamma: f32 },
+    CosineWarmRestarts { period: usize, period_mult: usize, min_lr: f32 },
+    LinearWarmup { warmup_epochs: usize },
+    OneCycle { pct_start: f32, div_factor: f32, final_div_factor: f32 },
+    ReduceOnPlateau { factor: f32, patience: usize, min_delta: f32, min_lr: f32 },
+}
+
+impl SchedulerConfig {
+    /// Every schedule with reasonable default hyperparameters.
+    pub const ALL: [SchedulerConfig; 7] = [
+        SchedulerConfig::Constant,
+        SchedulerConfig::StepDecay { step_size: 5, gamma: 0.5 },
+        SchedulerConfig::Exponential { gamma: 0.9 },
+        SchedulerConfig::CosineWarmRestarts { period: 5, period_mult: 2, min_lr: 0.0 },
+        SchedulerConfig::LinearWarmup { warmup_epochs: 1 },
+        SchedulerConfig::OneCycle { pct_start: 0.3, div_factor: 25.0, final_div_factor: 1e4 },
+        SchedulerConfig::ReduceOnPlateau { factor: 0.5, patience: 2, min_delta: 0.1, min_lr: 1e-5 },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchedulerConfig::Constant => "Constant",
+            SchedulerConfig::StepDecay { .. } => "Step Decay",
+            SchedulerConfig::Exponential { .. } => "Exponential Decay",
+            SchedulerConfig::CosineWarmRestarts { .. } => "Cosine Warm Restarts",
+            SchedulerConfig::LinearWarmup { .. } => "Linear Warmup",
+            SchedulerConfig::OneCycle { .. } => "One-Cycle",
+            SchedulerConfig::ReduceOnPlateau { .. } => "Reduce on Plateau",
+        }
+    }
+
+    pub fn build(&self) -> SchedulerState {
+        match *self {
+            SchedulerConfig::Constant => SchedulerState::Constant(Constant),
+            SchedulerConfig::StepDecay { step_size, gamma } => {
+                SchedulerState::StepDecay(StepDecay { step_size, gamma })
+            }
+            SchedulerConfig::Exponential { gamma } => SchedulerState::Exponential(ExponentialDecay { gamma }),
+            SchedulerConfig::CosineWarmRestarts { period, period_mult, min_lr } => {
+                SchedulerState::CosineWarmRestarts(CosineWarmRestarts { period, period_mult, min_lr })
+            }
+            SchedulerConfig::LinearWarmup { warmup_epochs } => {
+                SchedulerState::LinearWarmup(LinearWarmup { warmup_epochs })
+            }
+            SchedulerConfig::OneCycle { pct_start, div_factor, final_div_factor } => {
+                SchedulerState::OneCycle(OneCycle { pct_start, div_factor, final_div_factor })
+            }
+            SchedulerConfig::ReduceOnPlateau { factor, patience, min_delta, min_lr } => {
+                SchedulerState::ReduceOnPlateau(ReduceOnPlateau::new(factor, patience, min_delta, min_lr))
+            }
+        }
+    }
+}
+
+/// A built scheduler together with any state it has accumulated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SchedulerState {
+    Constant(Constant),
+    StepDecay(StepDecay),
+    Exponential(ExponentialDecay),
+    CosineWarmRestarts(CosineWarmRestarts),
+    LinearWarmup(LinearWarmup),
+    OneCycle(OneCycle),
+    ReduceOnPlateau(ReduceOnPlateau),
+}
+
+impl SchedulerState {
+    fn inner(&self) -> &dyn LrScheduler {
+        match self {
+            SchedulerState::Constant(s) => s,
+            SchedulerState::StepDecay(s) => s,
+            SchedulerState::Exponential(s) => s,
+            SchedulerState::CosineWarmRestarts(s) => s,
+            SchedulerState::LinearWarmup(s) => s,
+            SchedulerState::OneCycle(s) => s,
+            SchedulerState::ReduceOnPlateau(s) => s,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn LrScheduler {
+        match self {
+            SchedulerState::Constant(s) => s,
+            SchedulerState::StepDecay(s) => s,
+            SchedulerState::Exponential(s) => s,
+            SchedulerState::CosineWarmRestarts(s) => s,
+            SchedulerState::LinearWarmup(s) => s,
+            SchedulerState::OneCycle(s) => s,
+            SchedulerState::ReduceOnPlateau(s) => s,
+        }
+    }
+}
+
+impl LrScheduler for SchedulerState {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32 {
+        self.inner().learning_rate(base_lr, progress)
+    }
+
+    fn end_epoch(&mut self, validation_accuracy: f32) {
+        self.inner_mut().end_epoch(validation_accuracy);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Constant;
+
+impl LrScheduler for Constant {
+    fn learning_rate(&self, base_lr: f32, _: &ScheduleProgress) -> f32 {
+        base_lr
+    }
+}
+
+/// Multiplies the rate by `gamma` every `step_size` epochs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepDecay {
+    pub step_size: usize,
+    pub gamma: f32,
+}
+
+impl LrScheduler for StepDecay {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32 {
+        base_lr * self.gamma.powi((progress.epoch / self.step_size.max(1)) as i32)
+    }
+}
+
+/// Multiplies the rate by `gamma` every epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExponentialDecay {
+    pub gamma: f32,
+}
+
+impl LrScheduler for ExponentialDecay {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32 {
+        base_lr * self.gamma.powi(progress.epoch as i32)
+    }
+}
+
+/// SGDR: cosine annealing from the base rate down to `min_lr`, restarting
+/// after `period` epochs. Each period is `period_mult` times the previous one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CosineWarmRestarts {
+    pub period: usize,
+    pub period_mult: usize,
+    pub min_lr: f32,
+}
+
+impl LrScheduler for CosineWarmRestarts {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32 {
+        let mut period = self.period.max(1) as f32;
+        let mut elapsed = progress.epoch_fraction();
+        while elapsed >= period {
+            elapsed -= period;
+            period *= self.period_mult.max(1) as f32;
+        }
+        self.min_lr + (base_lr - self.min_lr) * (1.0 + (PI * elapsed / period).cos()) / 2.0
+    }
+}
+
+/// Ramps the rate linearly from zero to the base rate over the first
+/// `warmup_epochs` epochs, then holds it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinearWarmup {
+    pub warmup_epochs: usize,
+}
+
+impl LrScheduler for LinearWarmup {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32 {
+        let warmup_steps = self.warmup_epochs * progress.steps_per_epoch;
+        let step = progress.global_step();
+        if step < warmup_steps {
+            base_lr * (step + 1) as f32 / warmup_steps as f32
+        } else {
+            base_lr
+        }
+    }
+}
+
+/// One-cycle policy over the whole run: cosine ramp from
+/// `base_lr / div_factor` up to the base rate during the first `pct_start` of
+/// the steps, then cosine annealing down to `base_lr / div_factor / final_div_factor`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneCycle {
+    pub pct_start: f32,
+    pub div_factor: f32,
+    pub final_div_factor: f32,
+}
+
+impl LrScheduler for OneCycle {
+    fn learning_rate(&self, base_lr: f32, progress: &ScheduleProgress) -> f32 {
+        let initial_lr = base_lr / self.div_factor;
+        let final_lr = initial_lr / self.final_div_factor;
+        let position = progress.global_step() as f32 / progress.total_steps() as f32;
+        if position < self.pct_start {
+            cosine_interpolate(initial_lr, base_lr, position / self.pct_start)
+        } else {
+            cosine_interpolate(base_lr, final_lr, (position - self.pct_start) / (1.0 - self.pct_start).max(f32::EPSILON))
+        }
+    }
+}
+
+fn cosine_interpolate(start: f32, end: f32, fraction: f32) -> f32 {
+    end + (start - end) * (1.0 + (PI * fraction.clamp(0.0, 1.0)).cos()) / 2.0
+}
+
+/// Multiplies the rate by `factor` once validation accuracy has failed to
+/// improve by at least `min_delta` percentage points for more than `patience`
+/// epochs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReduceOnPlateau {
+    pub factor: f32,
+    pub patience: usize,
+    pub min_delta: f32,
+    pub min_lr: f32,
+    pub scale: f32,
+    pub best: Option<f32>,
+    pub epochs_without_improvement: usize,
+}
+
+impl ReduceOnPlateau {
+    pub fn new(factor: f32, patience: usize, min_delta: f32, min_lr: f32) -> Self {
+        ReduceOnPlateau {
+            factor,
+            patience,
+            min_delta,
+            min_lr,
+            scale: 1.0,
+            best: None,
+            epochs_without_improvement: 0,
+        }
+    }
+}
+
+impl LrScheduler for ReduceOnPlateau {
+    fn learning_rate(&self, base_lr: f32, _: &ScheduleProgress) -> f32 {
+        (base_lr * self.scale).max(self.min_lr)
+    }
+
+    fn end_epoch(&mut self, validation_accuracy: f32) {
+        match self.best {
+            Some(best) if validation_accuracy <= best + self.min_delta => {
+                self.epochs_without_improvement += 1;
+                if self.epochs_without_improvement > self.patience {
+                    self.scale *= self.factor;
+                    self.epochs_without_improvement = 0;
+                }
+            }
+            _ => {
+                self.best = Some(validation_accuracy);
+                self.epochs_without_improvement = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::error::{NeuralNetError, Result};
+use crate::network::layer::Layer;
+use crate::utils::math::is_finite;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What the trainer does with a batch whose loss, activations or gradients
+/// contain NaN or infinity, as stored in `Config`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DivergencePolicy {
+    /// Stop training with `NeuralNetError::Diverged`.
+    #[default]
+    Halt,
+    /// Drop the batch without updating any parameters.
+    SkipBatch,
+    /// Drop the batch and multiply the learning rate by `factor` for the rest
+    /// of the run.
+    ReduceLr { factor: f32 },
+}
+
+impl DivergencePolicy {
+    pub const ALL: [DivergencePolicy; 3] = [
+        DivergencePolicy::Halt,
+        DivergencePolicy::SkipBatch,
+        DivergencePolicy::ReduceLr { factor: 0.5 },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DivergencePolicy::Halt => "Halt",
+            DivergencePolicy::SkipBatch => "Skip Batch",
+            DivergencePolicy::ReduceLr { .. } => "Reduce LR",
+        }
+    }
+}
+
+/// Which values of a batch were not finite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NonFinite {
+    Activations,
+    Loss,
+    Gradients,
+}
+
+/// The first non-finite values found in a batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub source: NonFinite,
+    /// Index and kind of the layer holding them; `None` for the loss.
+    pub layer: Option<(usize, String)>,
+    pub epoch: usize,
+    pub step: usize,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self.source {
+            NonFinite::Activations => "activations",
+            NonFinite::Loss => "loss",
+            NonFinite::Gradients => "gradients",
+        };
+        write!(f, "non-finite {}", source)?;
+        if let Some((index, kind)) = &self.layer {
+            write!(f, " in layer {} ({})", index, kind)?;
+        }
+        write!(f, " at epoch {}, step {}", self.epoch + 1, self.step + 1)
+    }
+}
+
+/// Applies the `DivergencePolicy` over a run and remembers what went wrong.
+/// Lives as long as the run, since `ReduceLr` lowers the learning rate for
+/// every later epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DivergenceGuard {
+    policy: DivergencePolicy,
+    /// Multiplies the scheduled learning rate.
+    pub lr_scale: f32,
+    pub skipped_batches: usize,
+    /// The first divergence of the run.
+    pub first: Option<Divergence>,
+}
+
+impl DivergenceGuard {
+    pub fn new(policy: DivergencePolicy) -> Self {
+        DivergenceGuard { policy, lr_scale: 1.0, skipped_batches: 0, first: None }
+    }
+
+    /// Records `divergence`. Returns an error if training has to stop, and
+    /// otherwise the batch is to be dropped.
+    pub fn handle(&mut self, divergence: Divergence) -> Result<()> {
+        self.first.get_or_insert_with(|| divergence.clone());
+        match self.policy {
+            DivergencePolicy::Halt => return Err(NeuralNetError::Diverged(divergence)),
+            DivergencePolicy::SkipBatch => {}
+            DivergencePolicy::ReduceLr { factor } => self.lr_scale *= factor,
+        }
+        self.skipped_batches += 1;
+        Ok(())
+    }
+}
+
+/// Clamps every gradient to `[-clip_value, clip_value]`, then scales all
+/// gradients together so their global L2 norm is at most `clip_norm`. A limit
+/// of `0` disables that kind of clipping.
+pub fn clip_gradients(layers: &mut [Layer], clip_value: f32, clip_norm: f32) {
+    if clip_value > 0.0 {
+        for layer in layers.iter_mut() {
+            for mut grad in layer.gradients_mut() {
+                grad.mapv_inplace(|g| g.clamp(-clip_value, clip_value));
+            }
+        }
+    }
+    if clip_norm > 0.0 {
+        let squared: f32 = layers
+            .iter_mut()
+            .flat_map(|layer| layer.gradients_mut())
+            .map(|grad| grad.fold(0.0, |sum, &g| sum + g * g))
+            .fold(0.0, |total, sum| total + sum);
+        let norm = squared.sqrt();
+        if norm > clip_norm {
+            let scale = clip_norm / norm;
+            for layer in layers.iter_mut() {
+                for mut grad in layer.gradients_mut() {
+                    grad *= scale;
+                }
+            }
+        }
+    }
+}
+
+/// Index of the first layer reached by the backward pass, i.e. the last one,
+/// whose gradients contain NaN or infinity.
+pub(crate) fn non_finite_gradients(layers: &mut [Layer]) -> Option<usize> {
+    layers
+        .iter_mut()
+        .enumerate()
+        .rev()
+        .find_map(|(index, layer)| (!layer.gradients_mut().iter().all(is_finite)).then_some(index))
+}
@@ -1,116 +1,194 @@
-use crate::network::layer::Layer;
-use crate::data::dataset::Sample;
-use crate::utils::math::shuffle_dataset;
-use ndarray::Array1;
-use crate::network::activation::{Activation,softmax};
-
-pub fn forward_pass(layers: &mut [Layer], inputs: &Array1<f32>) {
-    let total_layers = layers.len();
-
-    for (i, neuron) in layers[0].neurons.iter_mut().enumerate() {
-        neuron.raw_value = inputs[i];
-        neuron.activated_value = inputs[i];
+use crate::network::dense::Dense;
+use crate::network::layer::{Layer, Mode};
+use crate::network::Network;
+use crate::data::dataset::{to_batch, Sample};
+use crate::config::Config;
+use crate::error::{NeuralNetError, Result};
+use crate::training::loss::{smooth_labels, Loss};
+use crate::training::optimizer::Optimizer;
+use crate::training::parallel::{hogwild_epoch, DataParallel, ParallelMode};
+use crate::training::regularization::Regularizer;
+use crate::training::scheduler::{LrScheduler, ScheduleProgress};
+use crate::training::stability::{clip_gradients, non_finite_gradients, Divergence, DivergenceGuard, NonFinite};
+use crate::utils::math::{is_finite, shuffle_dataset};
+use rand::Rng;
+use ndarray::Array2;
+
+/// Computes the gradients of every layer for the batch cached by
+/// `Network::forward_batch`; `targets` holds one sample per row and gradients
+/// are averaged over the batch. Parameters are left untouched; an `Optimizer`
+/// applies the update.
+pub fn back_propagate(network: &mut Network, targets: &Array2<f32>, loss: &dyn Loss) -> Result<()> {
+    let (output_layer, hidden_layers) = split_output_layer(network.layers_mut())?;
+
+    let outputs = &output_layer.activated_values;
+    let fused = output_layer
+        .activation
+        .and_then(|activation| loss.fused_gradient(activation, outputs, targets));
+    let mut gradient = match fused {
+        Some(deltas) => output_layer.backward_deltas(deltas),
+        None => output_layer.backward(&loss.gradient(outputs, targets)),
+    };
+
+    for layer in hidden_layers.iter_mut().rev() {
+        gradient = layer.backward(&gradient);
     }
+    Ok(())
+}
 
-    for l in 1..total_layers {
-        let prev_activations = layers[l - 1].activated_values();
-
-        let is_not_output = l < (total_layers - 1);
-
-        for neuron in &mut layers[l].neurons {
-            let weighted_sum = neuron.weights.dot(&prev_activations) + neuron.bias;
-            neuron.raw_value = weighted_sum;
-
-            if let Some(activation) = layers[l].activation {
-                match activation {
-                    Activation::Softmax => {
-                        neuron.activated_value = weighted_sum;
-                    }
-                    _ => {
-                        neuron.activated_value = activation.activate(weighted_sum)
-                    }
-                }
-            }
-
-
-            neuron.activated_value = if is_not_output {
-                let activation = layers[l].activation.unwrap();
-                activation.activate(weighted_sum)
-            } else {
-                weighted_sum // softmax will be applied later
-            };
-        }
+/// Non-finite values of a batch, with the index of the layer holding them.
+pub(crate) type NonFiniteAt = (NonFinite, Option<usize>);
+
+/// Forward and backward pass of `samples`, leaving their gradients in
+/// `network`. Returns the non-finite activations or loss that cut it short
+/// instead, if any.
+pub(crate) fn batch_gradients<R: Rng + ?Sized>(
+    network: &mut Network,
+    samples: &[&Sample],
+    config: &Config,
+    loss: &dyn Loss,
+    rng: &mut R,
+) -> Result<Option<NonFiniteAt>> {
+    let (inputs, targets) = to_batch(samples);
+    let targets = smooth_labels(&targets, config.label_smoothing);
+    let (outputs, non_finite_layer) = network.forward_batch_checked(&inputs, Mode::Train, rng)?;
+    if let Some(index) = non_finite_layer {
+        return Ok(Some((NonFinite::Activations, Some(index))));
     }
-
-    // apply softmax now
-    let output_index = total_layers - 1;
-    let raw_outputs: Array1<f32> = layers[output_index]
-        .neurons
-        .iter()
-        .map(|n| n.raw_value)
-        .collect();
-    let softmax_values = softmax(&raw_outputs);
-
-    for (neuron, &val) in layers[output_index].neurons.iter_mut().zip(softmax_values.iter()) {
-        neuron.activated_value = val;
+    if !is_finite(&loss.loss(&outputs, &targets)) {
+        return Ok(Some((NonFinite::Loss, None)));
     }
+    back_propagate(network, &targets, loss)?;
+    Ok(None)
 }
 
-pub fn back_propagate(layers: &mut [Layer], targets: &Array1<f32>, learning_rate: f32) {
-    let output_index = layers.len() - 1;
+/// Adds the penalty gradients and returns the first layer, searching from
+/// the output, whose gradients are not finite.
+pub(crate) fn finish_gradients(network: &mut Network, regularizer: &Regularizer) -> Option<NonFiniteAt> {
+    regularizer.add_gradients(network.layers_mut());
+    non_finite_gradients(network.layers_mut()).map(|index| (NonFinite::Gradients, Some(index)))
+}
 
-    for (i, neuron) in layers[output_index].neurons.iter_mut().enumerate() {
-        neuron.delta = neuron.activated_value - targets[i];
+/// Clips the gradients, steps the optimizer and applies weight decay.
+pub(crate) fn apply_step(
+    network: &mut Network,
+    optimizer: &mut dyn Optimizer,
+    regularizer: &Regularizer,
+    config: &Config,
+    learning_rate: f32,
+) {
+    clip_gradients(network.layers_mut(), config.clip_value, config.clip_norm);
+    optimizer.step(network.layers_mut(), learning_rate);
+    regularizer.decay(network.layers_mut(), learning_rate);
+}
+
+/// Splits the dense output layer from the layers before it, skipping the input layer.
+fn split_output_layer(layers: &mut [Layer]) -> Result<(&mut Dense, &mut [Layer])> {
+    let count = layers.len();
+    match layers.get_mut(1..).and_then(|trainable| trainable.split_last_mut()) {
+        Some((Layer::Dense(output_layer), hidden_layers)) => Ok((output_layer, hidden_layers)),
+        Some(_) => Err(NeuralNetError::InvalidConfig("the output layer must be a dense layer".to_string())),
+        None => Err(NeuralNetError::shape_mismatch("number of layers", 2, count)),
     }
+}
 
-    for l in (1..output_index).rev() {
-        let next_layer_deltas: Vec<f32> = layers[l + 1].neurons.iter().map(|n| n.delta).collect();
-        let next_layer_weights: Vec<Vec<f32>> = layers[l + 1].neurons.iter().map(|n| n.weights.to_vec()).collect();
-        let activation = layers[l].activation.unwrap();
+/// Runs one epoch of mini-batch training over a shuffled `training_set`,
+/// asking `scheduler` for the learning rate before every optimizer step. The
+/// penalties, weight decay and gradient clipping in `config` are applied
+/// around every step. A batch with a non-finite loss, activations or
+/// gradients never reaches the optimizer; `guard` decides whether training
+/// goes on without it, but an epoch that drops every batch fails with
+/// `NeuralNetError::Diverged`. The sample order is drawn from `rng`. Returns
+/// the learning rate used for the last step, or an error if the samples do
+/// not fit the network or training diverged.
+///
+/// With `config.threads` above one, the batches are trained on that many
+/// threads as `config.parallel_mode` describes.
+#[allow(clippy::too_many_arguments)]
+pub fn train_epoch<R: Rng + ?Sized>(
+    network: &mut Network,
+    training_set: &[Sample],
+    epoch: usize,
+    config: &Config,
+    optimizer: &mut dyn Optimizer,
+    scheduler: &dyn LrScheduler,
+    guard: &mut DivergenceGuard,
+    rng: &mut R,
+) -> Result<f32> {
+    network.check_dataset(training_set)?;
+    let regularizer = Regularizer::new(config, network)?;
+    let batch_size = config.batch_size.max(1);
+    let mut order: Vec<usize> = (0..training_set.len()).collect();
+    shuffle_dataset(&mut order, rng);
+
+    let mut progress = ScheduleProgress {
+        epoch,
+        step: 0,
+        steps_per_epoch: order.len().div_ceil(batch_size),
+        total_epochs: config.epochs,
+    };
+    let mut learning_rate = config.learning_rate;
+    let loss = config.loss.build();
+    // an epoch that drops every batch cannot make progress, whatever the policy
+    let mut updated = false;
+    let mut first_divergence = None;
+
+    let threads = config.threads.max(1);
+    if threads > 1 && config.parallel_mode == ParallelMode::Hogwild {
+        let batches: Vec<Vec<&Sample>> =
+            order.chunks(batch_size).map(|chunk| chunk.iter().map(|&i| &training_set[i]).collect()).collect();
+        let rates: Vec<f32> = (0..batches.len())
+            .map(|step| {
+                progress.step = step;
+                scheduler.learning_rate(config.learning_rate, &progress)
+            })
+            .collect();
+        (learning_rate, updated, first_divergence) =
+            hogwild_epoch(network, &batches, &rates, epoch, config, &regularizer, guard, rng)?;
+    } else {
+        let mut data_parallel = if threads > 1 { Some(DataParallel::new(network, threads)?) } else { None };
+        for (step, chunk) in order.chunks(batch_size).enumerate() {
+            progress.step = step;
+            learning_rate = scheduler.learning_rate(config.learning_rate, &progress) * guard.lr_scale;
+
+            let samples: Vec<&Sample> = chunk.iter().map(|&i| &training_set[i]).collect();
+            let non_finite = match data_parallel.as_mut() {
+                Some(data_parallel) => data_parallel.gradients(network, &samples, config, loss.as_ref(), rng)?,
+                None => batch_gradients(network, &samples, config, loss.as_ref(), rng)?,
+            };
+            if let Some((source, layer)) = non_finite.or_else(|| finish_gradients(network, &regularizer)) {
+                let layer = layer.map(|index| (index, network.layers()[index].kind().to_string()));
+                let divergence = Divergence { source, layer, epoch, step };
+                first_divergence.get_or_insert_with(|| divergence.clone());
+                guard.handle(divergence)?;
+                continue;
+            }
 
-        for (j, neuron) in layers[l].neurons.iter_mut().enumerate() {
-            let sum: f32 = next_layer_deltas.iter().zip(next_layer_weights.iter()).map(|(delta, weights)| delta * weights[j]).sum();
-            neuron.delta = sum * activation.derivate(neuron.activated_value);
+            apply_step(network, optimizer, &regularizer, config, learning_rate);
+            updated = true;
         }
     }
 
-    for l in 1..layers.len() {
-        let prev_activations = layers[l - 1].activated_values();
-
-        for neuron in &mut layers[l].neurons {
-            neuron.bias -= learning_rate * neuron.delta;
-
-            let gradient = &prev_activations * neuron.delta;
-            neuron.weights = &neuron.weights - &(gradient * learning_rate);
-        }
+    match first_divergence {
+        Some(divergence) if !updated => Err(NeuralNetError::Diverged(divergence)),
+        _ => Ok(learning_rate),
     }
 }
 
-/*fn calculate_loss(layers: &[Layer], targets: &Array1<f32>) -> f32 {
-    let output_index = layers.len() - 1;
-    layers[output_index]
-        .neurons
-        .iter()
-        .zip(targets.iter())
-        .map(|(neuron, &target)| -target * (neuron.activated_value + 1e-12).ln())
-        .sum()
-}*/
-
-pub fn train(
-    layers: &mut [Layer],
+/// Trains for every epoch in `config`. Schedulers that react to validation
+/// accuracy get no feedback here; callers that evaluate between epochs should
+/// use `train_epoch` and `LrScheduler::end_epoch` instead.
+pub fn train<R: Rng + ?Sized>(
+    network: &mut Network,
     training_set: &[Sample],
-    epochs: usize,
-    learning_rate: f32,
-    //test_set: &[Sample],
-) {
-    for _ in 0..epochs {
-        let mut shuffled = training_set.to_vec();
-        shuffle_dataset(&mut shuffled);
-
-        for sample in shuffled.iter() {
-            forward_pass(layers, &sample.inputs);
-            back_propagate(layers, &sample.target, learning_rate);
-        }
-
-        }
+    config: &Config,
+    optimizer: &mut dyn Optimizer,
+    scheduler: &dyn LrScheduler,
+    rng: &mut R,
+) -> Result<()> {
+    let mut guard = DivergenceGuard::new(config.on_divergence);
+    for epoch in 0..config.epochs {
+        train_epoch(network, training_set, epoch, config, optimizer, scheduler, &mut guard, rng)?;
+    }
+    Ok(())
 }
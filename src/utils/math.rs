@@ -1,9 +1,25 @@
+use ndarray::{ArrayBase, Data, Dimension};
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-pub fn shuffle_dataset<T>(dataset: &mut [T]) {
-    let mut rng = thread_rng();
-    dataset.shuffle(&mut rng);
+/// The generator behind every random choice made while building and training
+/// a network. Runs seeded with the same value are bit-for-bit reproducible.
+pub type NetRng = ChaCha8Rng;
+
+/// Seeds a `NetRng` from `seed`, or from the OS when there is none.
+pub fn seeded_rng(seed: Option<u64>) -> NetRng {
+    match seed {
+        Some(seed) => NetRng::seed_from_u64(seed),
+        None => NetRng::from_entropy(),
+    }
 }
 
+pub fn shuffle_dataset<T, R: Rng + ?Sized>(dataset: &mut [T], rng: &mut R) {
+    dataset.shuffle(rng);
+}
 
+/// Whether no value is NaN or infinite.
+pub fn is_finite<S: Data<Elem = f32>, D: Dimension>(values: &ArrayBase<S, D>) -> bool {
+    values.iter().all(|v| v.is_finite())
+}